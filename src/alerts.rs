@@ -0,0 +1,399 @@
+use crate::gateway::GatewayStatus;
+use crate::sampler::{Sampler, Snapshot};
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Alerting settings from the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// Channel that firing/resolved embeds are posted to, alerts only show in `/health` when unset
+    pub channel_id: Option<u64>,
+    pub evaluation_interval_secs: u64,
+    pub rules: Vec<AlertRule>,
+    /// Add a built-in rule that fires as soon as any sensor reaches its critical temperature
+    pub critical_temperature: bool,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            channel_id: None,
+            evaluation_interval_secs: 15,
+            rules: Vec::new(),
+            critical_temperature: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertMetric {
+    Cpu,
+    Memory,
+    Disk,
+    Latency,
+    /// Hottest sensor, or the one named by `sensor`, in °C
+    Temperature,
+    /// How far the hottest sensor is above its own critical point, in °C
+    CriticalTemperature,
+}
+
+/// Fires when `metric` stays above `threshold` for `for_secs`
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    pub metric: AlertMetric,
+    /// Only for `disk`: watch this mount instead of all disks combined
+    pub mount_point: Option<String>,
+    /// Only for `temperature`: watch this sensor label instead of the hottest one
+    pub sensor: Option<String>,
+    pub threshold: f64,
+    /// A firing alert only resolves once the value drops below this, defaults to `threshold`
+    pub clear_threshold: Option<f64>,
+    #[serde(default)]
+    pub for_secs: u64,
+    /// Minimum time between two firing notifications for this rule
+    #[serde(default = "default_cooldown")]
+    pub cooldown_secs: u64,
+}
+
+fn default_cooldown() -> u64 {
+    1800
+}
+
+impl AlertRule {
+    fn value(&self, snapshot: &Snapshot, gateway: &GatewayStatus) -> Option<f64> {
+        match self.metric {
+            AlertMetric::Cpu => Some(snapshot.cpu_usage()),
+            AlertMetric::Memory => Some(snapshot.memory().usage_percent()),
+            AlertMetric::Disk => match &self.mount_point {
+                Some(mount_point) => snapshot
+                    .disks
+                    .iter()
+                    .find(|disk| &disk.mount_point == mount_point)
+                    .map(|disk| disk.usage_percent()),
+                None => crate::commands::health::combined_disk_usage(&snapshot.disks),
+            },
+            AlertMetric::Latency => gateway.latency().map(|latency| latency.as_secs_f64() * 1000.0),
+            AlertMetric::Temperature => snapshot
+                .sensors
+                .iter()
+                .filter(|sensor| self.sensor.as_ref().is_none_or(|label| &sensor.label == label))
+                .map(|sensor| sensor.temperature as f64)
+                .reduce(f64::max),
+            AlertMetric::CriticalTemperature => snapshot
+                .sensors
+                .iter()
+                .filter_map(|sensor| sensor.critical.map(|critical| (sensor.temperature - critical) as f64))
+                .reduce(f64::max),
+        }
+    }
+
+    fn format(&self, value: f64) -> String {
+        match self.metric {
+            AlertMetric::Latency => format!("{:.0}ms", value),
+            AlertMetric::Temperature => format!("{:.1}°C", value),
+            AlertMetric::CriticalTemperature => format!("{:+.1}°C vs critical", value),
+            _ => format!("{:.1}%", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleState {
+    Ok,
+    Pending { since: DateTime<Utc> },
+    Firing { since: DateTime<Utc>, notified: bool },
+}
+
+/// A rule that is currently firing
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveAlert {
+    pub name: String,
+    pub since: DateTime<Utc>,
+    pub value: f64,
+    pub threshold: f64,
+}
+
+enum Transition {
+    Fired,
+    Resolved,
+}
+
+struct RuleTracker {
+    state: RuleState,
+    last_value: Option<f64>,
+    last_notified: Option<DateTime<Utc>>,
+}
+
+/// Evaluates alert rules against every sample and posts state changes
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    trackers: Mutex<Vec<RuleTracker>>,
+}
+
+impl AlertEngine {
+    pub fn new(config: &AlertConfig) -> Self {
+        let mut rules = config.rules.clone();
+        if config.critical_temperature {
+            rules.push(AlertRule {
+                name: "Sensor above critical temperature".to_string(),
+                metric: AlertMetric::CriticalTemperature,
+                mount_point: None,
+                sensor: None,
+                threshold: 0.0,
+                // Wait for it to cool down a little before calling it resolved
+                clear_threshold: Some(-5.0),
+                for_secs: 0,
+                cooldown_secs: default_cooldown(),
+            });
+        }
+
+        let trackers = rules
+            .iter()
+            .map(|_| RuleTracker {
+                state: RuleState::Ok,
+                last_value: None,
+                last_notified: None,
+            })
+            .collect();
+
+        Self {
+            rules,
+            trackers: Mutex::new(trackers),
+        }
+    }
+
+    pub fn active(&self) -> Vec<ActiveAlert> {
+        let trackers = self.trackers.lock().unwrap();
+        self.rules
+            .iter()
+            .zip(trackers.iter())
+            .filter_map(|(rule, tracker)| match tracker.state {
+                RuleState::Firing { since, .. } => Some(ActiveAlert {
+                    name: rule.name.clone(),
+                    since,
+                    value: tracker.last_value.unwrap_or(rule.threshold),
+                    threshold: rule.threshold,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Step every rule once, returning the notifications that should go out
+    fn evaluate(&self, snapshot: &Snapshot, gateway: &GatewayStatus) -> Vec<(usize, Transition, f64)> {
+        let now = Utc::now();
+        let mut trackers = self.trackers.lock().unwrap();
+        let mut notifications = Vec::new();
+
+        for (index, (rule, tracker)) in self.rules.iter().zip(trackers.iter_mut()).enumerate() {
+            // A metric we can't read right now (no latency yet, unmounted disk) changes nothing
+            let Some(value) = rule.value(snapshot, gateway) else {
+                continue;
+            };
+            tracker.last_value = Some(value);
+            let clear_threshold = rule.clear_threshold.unwrap_or(rule.threshold);
+
+            tracker.state = match tracker.state {
+                RuleState::Ok if value > rule.threshold => RuleState::Pending { since: now },
+                RuleState::Ok => RuleState::Ok,
+                RuleState::Pending { .. } if value <= rule.threshold => RuleState::Ok,
+                RuleState::Pending { since } => {
+                    if (now - since).num_seconds() >= rule.for_secs as i64 {
+                        let cooled_down = tracker.last_notified.is_none_or(|at| {
+                            (now - at).num_seconds() >= rule.cooldown_secs as i64
+                        });
+                        if cooled_down {
+                            tracker.last_notified = Some(now);
+                            notifications.push((index, Transition::Fired, value));
+                        }
+                        RuleState::Firing { since, notified: cooled_down }
+                    } else {
+                        RuleState::Pending { since }
+                    }
+                }
+                RuleState::Firing { notified, .. } if value < clear_threshold => {
+                    // Only announce the recovery of alerts we announced in the first place
+                    if notified {
+                        notifications.push((index, Transition::Resolved, value));
+                    }
+                    RuleState::Ok
+                }
+                firing => firing,
+            };
+        }
+
+        notifications
+    }
+
+    /// Evaluate the rules every `interval`, posting to `channel` when one is configured
+    pub fn spawn(
+        self: Arc<Self>,
+        sampler: Arc<Sampler>,
+        gateway: Arc<GatewayStatus>,
+        http: Arc<serenity::Http>,
+        channel: Option<serenity::ChannelId>,
+        interval: Duration,
+    ) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+
+                let notifications = self.evaluate(&sampler.snapshot(), &gateway);
+                for (index, transition, value) in notifications {
+                    let rule = &self.rules[index];
+                    match transition {
+                        Transition::Fired => tracing::warn!("Alert firing: {} ({})", rule.name, rule.format(value)),
+                        Transition::Resolved => tracing::info!("Alert resolved: {} ({})", rule.name, rule.format(value)),
+                    }
+
+                    let Some(channel) = channel else {
+                        continue;
+                    };
+                    let embed = alert_embed(rule, &transition, value);
+                    if let Err(e) = channel.send_message(&http, serenity::CreateMessage::new().embed(embed)).await {
+                        tracing::error!("Failed to post alert `{}`: {}", rule.name, e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn alert_embed(rule: &AlertRule, transition: &Transition, value: f64) -> serenity::CreateEmbed {
+    let (title, color) = match transition {
+        Transition::Fired => (format!("🚨 Alert firing: {}", rule.name), 0xff0000),
+        Transition::Resolved => (format!("✅ Alert resolved: {}", rule.name), 0x00ff00),
+    };
+
+    let mut embed = serenity::CreateEmbed::new()
+        .title(title)
+        .color(color)
+        .field("Current Value", rule.format(value), true)
+        .field("Threshold", rule.format(rule.threshold), true);
+    if let Some(mount_point) = &rule.mount_point {
+        embed = embed.field("Mount", mount_point, true);
+    }
+    if let Some(sensor) = &rule.sensor {
+        embed = embed.field("Sensor", sensor, true);
+    }
+
+    embed
+        .timestamp(Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Alerts"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_snapshot;
+    use crate::utils::SensorReading;
+
+    fn engine(cooldown_secs: u64) -> AlertEngine {
+        AlertEngine::new(&AlertConfig {
+            rules: vec![AlertRule {
+                name: "Memory".to_string(),
+                metric: AlertMetric::Memory,
+                mount_point: None,
+                sensor: None,
+                threshold: 80.0,
+                clear_threshold: Some(70.0),
+                for_secs: 0,
+                cooldown_secs,
+            }],
+            critical_temperature: false,
+            ..Default::default()
+        })
+    }
+
+    /// Evaluate one sample, naming the notifications that would go out
+    fn step(engine: &AlertEngine, memory_percent: u64) -> Vec<&'static str> {
+        engine
+            .evaluate(&test_snapshot(memory_percent, 0), &GatewayStatus::default())
+            .into_iter()
+            .map(|(_, transition, _)| match transition {
+                Transition::Fired => "fired",
+                Transition::Resolved => "resolved",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fires_after_pending_and_resolves_below_the_clear_threshold() {
+        let engine = engine(0);
+        assert!(step(&engine, 90).is_empty());
+        assert!(engine.active().is_empty());
+        assert_eq!(step(&engine, 90), ["fired"]);
+        assert_eq!(engine.active().len(), 1);
+
+        // Between the clear threshold and the threshold it keeps firing
+        assert!(step(&engine, 75).is_empty());
+        assert_eq!(engine.active().len(), 1);
+        assert_eq!(step(&engine, 65), ["resolved"]);
+        assert!(engine.active().is_empty());
+    }
+
+    #[test]
+    fn dropping_back_while_pending_never_fires() {
+        let engine = engine(0);
+        step(&engine, 90);
+        assert!(step(&engine, 75).is_empty());
+        assert!(step(&engine, 75).is_empty());
+        assert!(engine.active().is_empty());
+    }
+
+    #[test]
+    fn cooldown_silences_refiring_and_its_recovery() {
+        let engine = engine(1800);
+        step(&engine, 90);
+        assert_eq!(step(&engine, 90), ["fired"]);
+        assert_eq!(step(&engine, 65), ["resolved"]);
+
+        step(&engine, 90);
+        assert!(step(&engine, 90).is_empty());
+        assert_eq!(engine.active().len(), 1);
+        assert!(step(&engine, 65).is_empty());
+    }
+
+    #[test]
+    fn without_cooldown_refiring_notifies_again() {
+        let engine = engine(0);
+        step(&engine, 90);
+        step(&engine, 90);
+        step(&engine, 65);
+        step(&engine, 90);
+        assert_eq!(step(&engine, 90), ["fired"]);
+    }
+
+    #[test]
+    fn critical_temperature_ignores_sensors_without_a_critical_point_and_clears_after_cooling_down() {
+        let engine = AlertEngine::new(&AlertConfig::default());
+        let with_cpu_at = |temperature: f32| {
+            let mut snapshot = test_snapshot(50, 0);
+            snapshot.sensors = vec![
+                SensorReading { label: "CPU".to_string(), temperature, max: temperature, critical: Some(90.0) },
+                // No critical point, so never considered
+                SensorReading { label: "NVMe".to_string(), temperature: 120.0, max: 120.0, critical: None },
+            ];
+            snapshot
+        };
+        let step = |temperature: f32| engine.evaluate(&with_cpu_at(temperature), &GatewayStatus::default()).len();
+
+        assert_eq!(step(80.0), 0);
+        // `for_secs` is zero, so it only waits for the next evaluation
+        assert_eq!(step(91.0), 0);
+        assert_eq!(step(91.0), 1);
+        assert_eq!(step(87.0), 0);
+        assert_eq!(step(84.0), 1);
+    }
+}
@@ -0,0 +1,181 @@
+use super::ApiState;
+use axum::{extract::State, http::header, response::IntoResponse};
+use chrono::Utc;
+use std::fmt::Write;
+
+/// Prometheus text exposition of the values the bot already gathers
+pub async fn metrics(State(state): State<ApiState>) -> impl IntoResponse {
+    let snapshot = state.data.sampler.snapshot();
+    let system_info = &snapshot.system;
+    let disks = &snapshot.disks;
+    let discord_latency = state.data.gateway.latency();
+    let bot_uptime = (Utc::now() - state.data.start_time).num_seconds();
+
+    let mut out = MetricsWriter::default();
+
+    out.header("starust_memory_used_bytes", "Memory in use on the host", "gauge");
+    out.sample("starust_memory_used_bytes", &[], system_info.memory.used as f64);
+    out.header("starust_memory_total_bytes", "Total memory on the host", "gauge");
+    out.sample("starust_memory_total_bytes", &[], system_info.memory.total as f64);
+    out.header("starust_memory_available_bytes", "Memory available for new allocations", "gauge");
+    out.sample("starust_memory_available_bytes", &[], system_info.memory.available as f64);
+
+    let container = &snapshot.container;
+    if let Some(limit) = container.memory_limit {
+        out.header("starust_cgroup_memory_limit_bytes", "Memory limit of the bot's cgroup", "gauge");
+        out.sample("starust_cgroup_memory_limit_bytes", &[], limit as f64);
+    }
+    if let Some(usage) = container.memory_usage {
+        out.header("starust_cgroup_memory_usage_bytes", "Memory used by the bot's cgroup", "gauge");
+        out.sample("starust_cgroup_memory_usage_bytes", &[], usage as f64);
+    }
+    if let Some(quota) = container.cpu_quota {
+        out.header("starust_cgroup_cpu_quota_cores", "CPU quota of the bot's cgroup", "gauge");
+        out.sample("starust_cgroup_cpu_quota_cores", &[], quota);
+    }
+    if let Some(limit) = container.pids_limit {
+        out.header("starust_cgroup_pids_limit", "Process limit of the bot's cgroup", "gauge");
+        out.sample("starust_cgroup_pids_limit", &[], limit as f64);
+    }
+    if let Some(current) = container.pids_current {
+        out.header("starust_cgroup_pids_current", "Processes in the bot's cgroup", "gauge");
+        out.sample("starust_cgroup_pids_current", &[], current as f64);
+    }
+
+    out.header("starust_disk_total_bytes", "Size of each mounted disk", "gauge");
+    for disk in disks {
+        out.sample("starust_disk_total_bytes", &disk_labels(disk), disk.total as f64);
+    }
+    out.header("starust_disk_available_bytes", "Free space on each mounted disk", "gauge");
+    for disk in disks {
+        out.sample("starust_disk_available_bytes", &disk_labels(disk), disk.available as f64);
+    }
+    out.header("starust_disk_usage_percent", "Used space on each mounted disk", "gauge");
+    for disk in disks {
+        out.sample("starust_disk_usage_percent", &disk_labels(disk), disk.usage_percent());
+    }
+
+    out.header("starust_network_received_bytes_total", "Bytes received on each interface", "counter");
+    for interface in &snapshot.networks {
+        out.sample("starust_network_received_bytes_total", &[("interface", &interface.name)], interface.rx_bytes as f64);
+    }
+    out.header("starust_network_transmitted_bytes_total", "Bytes sent on each interface", "counter");
+    for interface in &snapshot.networks {
+        out.sample("starust_network_transmitted_bytes_total", &[("interface", &interface.name)], interface.tx_bytes as f64);
+    }
+    out.header("starust_network_errors_total", "Receive and transmit errors on each interface", "counter");
+    for interface in &snapshot.networks {
+        out.sample("starust_network_errors_total", &[("interface", &interface.name), ("direction", "rx")], interface.rx_errors as f64);
+        out.sample("starust_network_errors_total", &[("interface", &interface.name), ("direction", "tx")], interface.tx_errors as f64);
+    }
+
+    out.header("starust_sensor_temperature_celsius", "Current temperature of each hardware sensor", "gauge");
+    for sensor in &snapshot.sensors {
+        out.sample("starust_sensor_temperature_celsius", &[("sensor", &sensor.label)], sensor.temperature as f64);
+    }
+    out.header("starust_sensor_critical_celsius", "Critical temperature of each hardware sensor", "gauge");
+    for sensor in &snapshot.sensors {
+        if let Some(critical) = sensor.critical {
+            out.sample("starust_sensor_critical_celsius", &[("sensor", &sensor.label)], critical as f64);
+        }
+    }
+
+    out.header("starust_cpu_usage_percent", "CPU usage", "gauge");
+    out.sample("starust_cpu_usage_percent", &[], system_info.cpu.usage as f64);
+    out.header("starust_cpu_core_usage_percent", "CPU usage of each logical core", "gauge");
+    for (core, usage) in system_info.cpu.per_core_usage.iter().enumerate() {
+        out.sample("starust_cpu_core_usage_percent", &[("core", &core.to_string())], *usage as f64);
+    }
+    out.header("starust_load_average", "System load average", "gauge");
+    let load = &system_info.cpu.load_average;
+    for (window, value) in [("1m", load.one), ("5m", load.five), ("15m", load.fifteen)] {
+        out.sample("starust_load_average", &[("window", window)], value);
+    }
+
+    out.header("starust_host_uptime_seconds", "Time since the host booted", "gauge");
+    out.sample("starust_host_uptime_seconds", &[], system_info.uptime as f64);
+
+    out.header("starust_bot_uptime_seconds", "Time since the bot started", "gauge");
+    out.sample("starust_bot_uptime_seconds", &[], bot_uptime as f64);
+
+    if let Some(latency) = discord_latency {
+        out.header("starust_gateway_latency_seconds", "Discord gateway heartbeat latency", "gauge");
+        out.sample("starust_gateway_latency_seconds", &[], latency.as_secs_f64());
+    }
+
+    let measured = state.data.latency.report();
+    for (name, help, measurement) in [
+        ("starust_rest_latency_seconds", "Round-trip of the last timed Discord REST request", measured.rest),
+        ("starust_reply_latency_seconds", "Time from picking up a command to Discord accepting its reply", measured.reply),
+        ("starust_dispatch_delay_seconds", "Time from the invoking snowflake's timestamp to the bot handling it", measured.dispatch_delay),
+    ] {
+        if let Some(measurement) = measurement {
+            out.header(name, help, "gauge");
+            out.sample(name, &[], measurement.ms / 1000.0);
+        }
+    }
+
+    out.header("starust_commands_total", "Command invocations recorded in the stats database", "counter");
+    // Lifetime counters from the stats database, so they don't reset on restarts or when old invocations are pruned
+    let totals = state.data.storage.read(|storage| storage.lifetime_totals()).await.unwrap_or_else(|e| {
+        tracing::warn!("Could not read command stats for /metrics: {}", e);
+        Vec::new()
+    });
+    for (command, totals) in &totals {
+        out.sample("starust_commands_total", &[("command", command)], totals.uses as f64);
+    }
+
+    out.header("starust_command_failures_total", "Command invocations that errored or were rejected", "counter");
+    for (command, totals) in &totals {
+        out.sample("starust_command_failures_total", &[("command", command)], totals.failures as f64);
+    }
+
+    out.header("starust_command_duration_seconds_total", "Time spent running each command", "counter");
+    for (command, totals) in &totals {
+        out.sample("starust_command_duration_seconds_total", &[("command", command)], totals.total_duration.as_secs_f64());
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        out.finish(),
+    )
+}
+
+fn disk_labels(disk: &crate::utils::DiskUsage) -> [(&str, &str); 2] {
+    [("device", &disk.name), ("mount_point", &disk.mount_point)]
+}
+
+#[derive(Default)]
+struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+
+    fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,285 @@
+use crate::alerts::ActiveAlert;
+use crate::cgroup::ContainerInfo;
+use crate::checks::CheckResult;
+use crate::latency::LatencyReport;
+use crate::monitors::{Incident, MonitorStatus};
+use crate::commands::health::{check_disk_health, combined_disk_usage, HealthStatus};
+use crate::scoring::{self, ComponentScore, HealthInputs};
+use crate::systemd::UnitStatus;
+use crate::utils::SensorReading;
+use crate::utils::{get_local_ip, SystemInfo, SCHEMA_VERSION};
+use crate::visibility::{SensitiveField, View};
+use crate::{Data, Error};
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+mod metrics;
+
+/// Everything the HTTP handlers need, shared with the Discord side
+#[derive(Clone)]
+pub struct ApiState {
+    pub data: Data,
+    /// Heartbeats older than this make `/readyz` fail
+    pub heartbeat_max_age: Duration,
+}
+
+#[derive(Serialize)]
+struct SystemResponse {
+    schema_version: u32,
+    system: SystemInfo,
+    container: ContainerInfo,
+    local_ip: Option<String>,
+    /// Public IPv4 address, kept under its old name
+    public_ip: Option<String>,
+    public_ipv6: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    schema_version: u32,
+    status: HealthStatus,
+    /// Weighted health score out of 100, `components` shows how it came about
+    score: f64,
+    components: Vec<ComponentScore>,
+    /// Relative to the cgroup memory limit when `memory_limited` is set
+    memory_usage_percent: f64,
+    memory_limited: bool,
+    disk_usage_percent: Option<f64>,
+    disk_status: String,
+    sensors: Vec<SensorReading>,
+    services: Vec<UnitStatus>,
+    checks: Vec<CheckResult>,
+    /// When `services` and `checks` were last probed
+    probed_at: DateTime<Utc>,
+    discord_latency_ms: Option<u128>,
+    latency: LatencyResponse,
+    api_version: &'static str,
+    alerts: Vec<ActiveAlert>,
+}
+
+#[derive(Serialize)]
+struct LatencyResponse {
+    gateway_ms: Option<u128>,
+    /// Over the recent heartbeats
+    gateway_avg_ms: Option<f64>,
+    gateway_max_ms: Option<f64>,
+    #[serde(flatten)]
+    measured: LatencyReport,
+}
+
+#[derive(Serialize)]
+struct MonitorsResponse {
+    schema_version: u32,
+    monitors: Vec<MonitorStatus>,
+    incidents: Vec<Incident>,
+}
+
+#[derive(Serialize)]
+struct ReadyResponse {
+    ready: bool,
+    gateway_connected: bool,
+    commands_registered: bool,
+    last_heartbeat_secs_ago: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct UptimeResponse {
+    schema_version: u32,
+    started_at: DateTime<Utc>,
+    uptime_secs: u64,
+}
+
+/// Serve the JSON API until the process exits
+pub async fn serve(addr: SocketAddr, state: ApiState) -> Result<(), Error> {
+    let app = Router::new()
+        .route("/v1/system", get(system))
+        .route("/v1/health", get(health))
+        .route("/v1/uptime", get(uptime))
+        .route("/v1/monitors", get(monitors))
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("HTTP API listening on {}", addr);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// Host details as the visibility policy shows them to anyone, since the API has no authentication
+async fn system(State(state): State<ApiState>) -> Json<SystemResponse> {
+    let view = View::public(&state.data.config.visibility);
+    let snapshot = state.data.sampler.snapshot();
+    let public_ip = state.data.public_ip.get().await;
+
+    let mut system = snapshot.system.clone();
+    system.kernel_version = view.show(SensitiveField::KernelVersion, &system.kernel_version).unwrap_or_default();
+    system.os_version = view.show(SensitiveField::OsVersion, &system.os_version).unwrap_or_default();
+
+    Json(SystemResponse {
+        schema_version: SCHEMA_VERSION,
+        system,
+        container: snapshot.container.clone(),
+        local_ip: get_local_ip().and_then(|ip| view.show(SensitiveField::LocalIp, &ip)),
+        public_ip: public_ip.v4.and_then(|ip| view.show(SensitiveField::PublicIp, &ip.to_string())),
+        public_ipv6: public_ip.v6.and_then(|ip| view.show(SensitiveField::PublicIp, &ip.to_string())),
+    })
+}
+
+async fn health(State(state): State<ApiState>) -> Json<HealthResponse> {
+    let snapshot = state.data.sampler.snapshot();
+    let memory_usage = snapshot.memory().usage_percent();
+    let discord_latency = state.data.gateway.latency();
+    let heartbeats = state.data.gateway.heartbeat_summary();
+    let alerts = state.data.alerts.active();
+    // Probing on every request would let anyone hammer the checked services, serve the background loop's results
+    let probed = match state.data.checks.latest() {
+        Some(probed) => probed,
+        None => std::sync::Arc::new(state.data.checks.probe(&state.data.config.systemd_units).await),
+    };
+    let (services, checks) = (&probed.services, &probed.checks);
+
+    // There is no command to time here, the background probe's last REST round-trip stands in
+    let report = scoring::evaluate(
+        &state.data.config,
+        &HealthInputs {
+            snapshot: &snapshot,
+            gateway_latency: discord_latency,
+            rest_latency: state.data.latency.rest(),
+            services,
+            checks,
+            checks_status: state.data.checks.overall(checks),
+            alerts: &alerts,
+        },
+    );
+
+    Json(HealthResponse {
+        schema_version: SCHEMA_VERSION,
+        status: report.status,
+        score: report.score,
+        components: report.components,
+        memory_usage_percent: memory_usage,
+        memory_limited: snapshot.memory_limited(),
+        disk_usage_percent: combined_disk_usage(&snapshot.disks),
+        disk_status: check_disk_health(&snapshot.disks, &state.data.config),
+        sensors: snapshot.sensors.clone(),
+        services: services.clone(),
+        checks: checks.clone(),
+        probed_at: probed.checked_at,
+        discord_latency_ms: discord_latency.map(|latency| latency.as_millis()),
+        latency: LatencyResponse {
+            gateway_ms: discord_latency.map(|latency| latency.as_millis()),
+            gateway_avg_ms: heartbeats.map(|(summary, _)| summary.avg),
+            gateway_max_ms: heartbeats.map(|(summary, _)| summary.max),
+            measured: state.data.latency.report(),
+        },
+        api_version: "v2.0.0",
+        alerts,
+    })
+}
+
+async fn uptime(State(state): State<ApiState>) -> Json<UptimeResponse> {
+    let uptime_duration = Utc::now() - state.data.start_time;
+
+    Json(UptimeResponse {
+        schema_version: SCHEMA_VERSION,
+        started_at: state.data.start_time,
+        uptime_secs: uptime_duration.num_seconds() as u64,
+    })
+}
+
+async fn monitors(State(state): State<ApiState>) -> Json<MonitorsResponse> {
+    Json(MonitorsResponse {
+        schema_version: SCHEMA_VERSION,
+        monitors: state.data.monitors.statuses(),
+        incidents: state.data.monitors.incidents(50),
+    })
+}
+
+/// Liveness: answering at all means the process and its runtime are alive
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness: the gateway is connected, commands are registered and heartbeats are flowing
+async fn readyz(State(state): State<ApiState>) -> (StatusCode, Json<ReadyResponse>) {
+    let gateway = &state.data.gateway;
+    let heartbeat_age = gateway.heartbeat_age();
+
+    let ready = gateway.is_connected()
+        && gateway.commands_registered()
+        && heartbeat_age.is_some_and(|age| age <= state.heartbeat_max_age);
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(ReadyResponse {
+            ready,
+            gateway_connected: gateway.is_connected(),
+            commands_registered: gateway.commands_registered(),
+            last_heartbeat_secs_ago: heartbeat_age.map(|age| age.as_secs()),
+        }),
+    )
+}
+
+/// Where a local client reaches `/readyz` on an instance bound to `api_addr`
+fn readyz_url(mut api_addr: SocketAddr) -> String {
+    // A wildcard bind address isn't something we can connect to, its loopback is
+    match api_addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => api_addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+        IpAddr::V6(ip) if ip.is_unspecified() => api_addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+        _ => {}
+    }
+    // `SocketAddr` brackets IPv6 addresses, as URLs need
+    format!("http://{}/readyz", api_addr)
+}
+
+/// `starust-api healthcheck [url]`: query a running instance's `/readyz` and
+/// return the process exit code, for use in a Docker `HEALTHCHECK`
+pub async fn healthcheck(api_addr: SocketAddr, url: Option<String>) -> i32 {
+    let url = url.unwrap_or_else(|| readyz_url(api_addr));
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(5)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("healthcheck: {}", e);
+            return 1;
+        }
+    };
+
+    match client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => {
+            println!("healthcheck: ready");
+            0
+        }
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            eprintln!("healthcheck: not ready ({}) {}", status, body);
+            1
+        }
+        Err(e) => {
+            eprintln!("healthcheck: {} unreachable: {}", url, e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readyz_url_reaches_wildcard_binds_over_loopback() {
+        let url = |addr: &str| readyz_url(addr.parse().unwrap());
+        assert_eq!(url("127.0.0.1:8080"), "http://127.0.0.1:8080/readyz");
+        assert_eq!(url("0.0.0.0:8080"), "http://127.0.0.1:8080/readyz");
+        assert_eq!(url("[::1]:8080"), "http://[::1]:8080/readyz");
+        assert_eq!(url("[::]:9000"), "http://[::1]:9000/readyz");
+    }
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// cgroup v1 reports "no limit" as a huge page-aligned number instead of `max`
+const V1_UNLIMITED: u64 = 1 << 62;
+
+/// What the bot knows about the container it runs in, if any
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContainerInfo {
+    pub containerized: bool,
+    /// `docker`, `podman` or `kubernetes` when it can be told apart
+    pub runtime: Option<String>,
+    pub cgroup_version: Option<u8>,
+    pub memory_limit: Option<u64>,
+    pub memory_usage: Option<u64>,
+    /// CPU quota in cores, e.g. `0.5` for `cpu.max = 50000 100000`
+    pub cpu_quota: Option<f64>,
+    /// Total CPU time consumed by the cgroup, in microseconds
+    #[serde(skip)]
+    pub cpu_usage_usec: Option<u64>,
+    /// CPU usage relative to the quota (or all host cores without one), set by the sampler
+    pub cpu_usage_percent: Option<f64>,
+    pub pids_limit: Option<u64>,
+    pub pids_current: Option<u64>,
+}
+
+/// Read the current cgroup's limits and usage
+pub fn detect() -> ContainerInfo {
+    let runtime = detect_runtime();
+    let mut info = ContainerInfo {
+        containerized: runtime.is_some() || in_container_cgroup(),
+        runtime,
+        ..Default::default()
+    };
+
+    let root = Path::new("/sys/fs/cgroup");
+    if root.join("cgroup.controllers").exists() {
+        let dir = cgroup_dir(root, None);
+        info.cgroup_version = Some(2);
+        info.memory_limit = read_limit(&dir.join("memory.max"));
+        info.memory_usage = read_u64(&dir.join("memory.current"));
+        info.cpu_quota = read_cpu_max(&dir.join("cpu.max"));
+        info.cpu_usage_usec = read_cpu_stat_usage(&dir.join("cpu.stat"));
+        info.pids_limit = read_limit(&dir.join("pids.max"));
+        info.pids_current = read_u64(&dir.join("pids.current"));
+    } else if root.join("memory").exists() {
+        info.cgroup_version = Some(1);
+        let memory = cgroup_dir(&root.join("memory"), Some("memory"));
+        info.memory_limit = read_limit(&memory.join("memory.limit_in_bytes"));
+        info.memory_usage = read_u64(&memory.join("memory.usage_in_bytes"));
+
+        let cpu = cgroup_dir(&root.join("cpu"), Some("cpu"));
+        let quota = read_u64(&cpu.join("cpu.cfs_quota_us"));
+        let period = read_u64(&cpu.join("cpu.cfs_period_us"));
+        info.cpu_quota = match (quota, period) {
+            (Some(quota), Some(period)) if period > 0 => Some(quota as f64 / period as f64),
+            _ => None,
+        };
+        let cpuacct = cgroup_dir(&root.join("cpuacct"), Some("cpuacct"));
+        info.cpu_usage_usec = read_u64(&cpuacct.join("cpuacct.usage")).map(|nanos| nanos / 1000);
+
+        let pids = cgroup_dir(&root.join("pids"), Some("pids"));
+        info.pids_limit = read_limit(&pids.join("pids.max"));
+        info.pids_current = read_u64(&pids.join("pids.current"));
+    }
+
+    info
+}
+
+fn detect_runtime() -> Option<String> {
+    if std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        Some("kubernetes".to_string())
+    } else if Path::new("/.dockerenv").exists() {
+        Some("docker".to_string())
+    } else if Path::new("/run/.containerenv").exists() {
+        Some("podman".to_string())
+    } else {
+        None
+    }
+}
+
+fn in_container_cgroup() -> bool {
+    std::fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| ["docker", "kubepods", "containerd", "libpod", "lxc"].iter().any(|marker| cgroup.contains(marker)))
+        .unwrap_or(false)
+}
+
+/// Directory of our own cgroup below `mount`, for the v1 `controller` or the v2 unified hierarchy.
+/// Inside a cgroup namespace our path is `/`, so this usually is `mount` itself.
+fn cgroup_dir(mount: &Path, controller: Option<&str>) -> PathBuf {
+    let own_path = std::fs::read_to_string("/proc/self/cgroup")
+        .ok()
+        .and_then(|cgroup| own_cgroup_path(&cgroup, controller));
+
+    match own_path {
+        Some(path) if !path.is_empty() && mount.join(&path).exists() => mount.join(path),
+        _ => mount.to_path_buf(),
+    }
+}
+
+/// Our path in `/proc/self/cgroup` for the v1 `controller` or the v2 unified hierarchy, relative to its mount
+fn own_cgroup_path(cgroup: &str, controller: Option<&str>) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let mut parts = line.splitn(3, ':');
+        let (_, controllers, path) = (parts.next()?, parts.next()?, parts.next()?);
+        let matches = match controller {
+            None => controllers.is_empty(),
+            Some(controller) => controllers.split(',').any(|c| c == controller),
+        };
+        matches.then(|| path.trim_start_matches('/').to_string())
+    })
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// A limit file holding either a number or `max`
+fn read_limit(path: &Path) -> Option<u64> {
+    parse_limit(&std::fs::read_to_string(path).ok()?)
+}
+
+fn parse_limit(raw: &str) -> Option<u64> {
+    raw.trim().parse().ok().filter(|limit| *limit < V1_UNLIMITED)
+}
+
+fn read_cpu_max(path: &Path) -> Option<f64> {
+    parse_cpu_max(&std::fs::read_to_string(path).ok()?)
+}
+
+/// `cpu.max` as cores, `None` for `max` (no quota)
+fn parse_cpu_max(raw: &str) -> Option<f64> {
+    let mut parts = raw.split_whitespace();
+    let quota: f64 = parts.next()?.parse().ok()?;
+    let period: f64 = parts.next()?.parse().ok()?;
+    (period > 0.0).then(|| quota / period)
+}
+
+fn read_cpu_stat_usage(path: &Path) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.lines().find_map(|line| {
+        line.strip_prefix("usage_usec ").and_then(|value| value.trim().parse().ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_max_is_quota_over_period() {
+        assert_eq!(parse_cpu_max("50000 100000\n"), Some(0.5));
+        assert_eq!(parse_cpu_max("200000 100000\n"), Some(2.0));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("50000 0\n"), None);
+        assert_eq!(parse_cpu_max(""), None);
+    }
+
+    #[test]
+    fn limits_treat_max_and_the_v1_sentinel_as_unlimited() {
+        assert_eq!(parse_limit("536870912\n"), Some(536_870_912));
+        assert_eq!(parse_limit("max\n"), None);
+        assert_eq!(parse_limit("9223372036854771712\n"), None);
+    }
+
+    #[test]
+    fn own_path_from_the_v2_unified_hierarchy() {
+        let cgroup = "0::/system.slice/starust.service\n";
+        assert_eq!(own_cgroup_path(cgroup, None).as_deref(), Some("system.slice/starust.service"));
+        assert_eq!(own_cgroup_path("0::/\n", None).as_deref(), Some(""));
+        assert_eq!(own_cgroup_path(cgroup, Some("memory")), None);
+    }
+
+    #[test]
+    fn own_path_from_a_v1_controller_list() {
+        let cgroup = "12:pids:/docker/abc123\n\
+                      4:cpu,cpuacct:/docker/abc123\n\
+                      3:memory:/docker/abc123\n\
+                      1:name=systemd:/docker/abc123\n";
+        assert_eq!(own_cgroup_path(cgroup, Some("memory")).as_deref(), Some("docker/abc123"));
+        assert_eq!(own_cgroup_path(cgroup, Some("cpuacct")).as_deref(), Some("docker/abc123"));
+        assert_eq!(own_cgroup_path(cgroup, Some("blkio")), None);
+        assert_eq!(own_cgroup_path(cgroup, None), None);
+    }
+
+    #[test]
+    fn cgroup_dir_falls_back_to_the_mount() {
+        let mount = Path::new("/nonexistent/cgroup");
+        assert_eq!(cgroup_dir(mount, Some("memory")), mount);
+    }
+}
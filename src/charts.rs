@@ -0,0 +1,156 @@
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+// Charts are rendered on the CPU and carry no text: titles, axes and exact
+// numbers live in the embed fields next to them, the image only shows the shape.
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 300;
+const PADDING: f32 = 16.0;
+const GRID_LINES: u32 = 4;
+
+/// Discord's dark theme, so the charts blend into the embed
+const BACKGROUND: u32 = 0x2f3136;
+const GRID: u32 = 0x40444b;
+
+/// Line chart of `values` in order, scaled from 0 (or the minimum if negative) to the maximum
+pub fn line_chart(values: &[f64], color: u32) -> Option<Vec<u8>> {
+    if values.len() < 2 {
+        return None;
+    }
+
+    let mut canvas = Canvas::new()?;
+    let (low, high) = value_range(values);
+
+    let step = canvas.plot_width() / (values.len() - 1) as f32;
+    let bottom = HEIGHT as f32 - PADDING;
+    let mut line = PathBuilder::new();
+    let mut area = PathBuilder::new();
+    area.move_to(PADDING, bottom);
+    for (i, value) in values.iter().enumerate() {
+        let x = PADDING + i as f32 * step;
+        let y = canvas.y_for(*value, low, high);
+        if i == 0 {
+            line.move_to(x, y);
+        } else {
+            line.line_to(x, y);
+        }
+        area.line_to(x, y);
+    }
+    area.line_to(PADDING + canvas.plot_width(), bottom);
+    area.close();
+    let line = line.finish()?;
+
+    // Shade the area under the line faintly, then draw the line itself on top
+    if let Some(area) = area.finish() {
+        canvas.pixmap.fill_path(&area, &paint(color, 60), FillRule::Winding, Transform::identity(), None);
+    }
+
+    let stroke = Stroke {
+        width: 3.0,
+        ..Stroke::default()
+    };
+    canvas.pixmap.stroke_path(&line, &paint(color, 255), &stroke, Transform::identity(), None);
+
+    canvas.png()
+}
+
+/// One bar per value, in order
+pub fn bar_chart(values: &[u64], color: u32) -> Option<Vec<u8>> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut canvas = Canvas::new()?;
+    let high = values.iter().copied().max().unwrap_or(0).max(1) as f64;
+
+    let slot = canvas.plot_width() / values.len() as f32;
+    let gap = (slot * 0.2).min(12.0);
+    let bottom = HEIGHT as f32 - PADDING;
+    for (i, value) in values.iter().enumerate() {
+        let top = canvas.y_for(*value as f64, 0.0, high);
+        let x = PADDING + i as f32 * slot + gap / 2.0;
+        // Keep a sliver visible for zero counts so the slot is still readable
+        let rect = Rect::from_ltrb(x, top.min(bottom - 1.0), x + slot - gap, bottom)?;
+        canvas.pixmap.fill_rect(rect, &paint(color, 255), Transform::identity(), None);
+    }
+
+    canvas.png()
+}
+
+/// Bar chart of how often each face from 1 to `sides` came up
+pub fn histogram(rolls: &[u32], sides: u32, color: u32) -> Option<Vec<u8>> {
+    // Past ~50 faces single-pixel bars stop being readable, so group faces together
+    let buckets = sides.min(50) as usize;
+    let mut counts = vec![0u64; buckets];
+    for roll in rolls {
+        let bucket = ((roll - 1) as u64 * buckets as u64 / sides as u64) as usize;
+        counts[bucket.min(buckets - 1)] += 1;
+    }
+
+    bar_chart(&counts, color)
+}
+
+struct Canvas {
+    pixmap: Pixmap,
+}
+
+impl Canvas {
+    fn new() -> Option<Self> {
+        let mut pixmap = Pixmap::new(WIDTH, HEIGHT)?;
+        pixmap.fill(rgb(BACKGROUND, 255));
+
+        let mut canvas = Canvas { pixmap };
+        canvas.draw_grid();
+        Some(canvas)
+    }
+
+    fn plot_width(&self) -> f32 {
+        WIDTH as f32 - 2.0 * PADDING
+    }
+
+    fn plot_height(&self) -> f32 {
+        HEIGHT as f32 - 2.0 * PADDING
+    }
+
+    fn y_for(&self, value: f64, low: f64, high: f64) -> f32 {
+        let fraction = if high > low { (value - low) / (high - low) } else { 0.5 };
+        HEIGHT as f32 - PADDING - fraction as f32 * self.plot_height()
+    }
+
+    fn draw_grid(&mut self) {
+        let grid = paint(GRID, 255);
+        for i in 0..=GRID_LINES {
+            let y = PADDING + i as f32 * self.plot_height() / GRID_LINES as f32;
+            if let Some(rect) = Rect::from_xywh(PADDING, y, self.plot_width(), 1.0) {
+                self.pixmap.fill_rect(rect, &grid, Transform::identity(), None);
+            }
+        }
+    }
+
+    fn png(self) -> Option<Vec<u8>> {
+        match self.pixmap.encode_png() {
+            Ok(png) => Some(png),
+            Err(e) => {
+                tracing::warn!("Failed to encode chart: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn value_range(values: &[f64]) -> (f64, f64) {
+    let low = values.iter().copied().fold(f64::INFINITY, f64::min).min(0.0);
+    let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    (low, high)
+}
+
+fn rgb(color: u32, alpha: u8) -> Color {
+    Color::from_rgba8((color >> 16) as u8, (color >> 8) as u8, color as u8, alpha)
+}
+
+fn paint(color: u32, alpha: u8) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(rgb(color, alpha));
+    paint.anti_alias = true;
+    paint
+}
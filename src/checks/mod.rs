@@ -0,0 +1,298 @@
+use crate::commands::health::HealthStatus;
+use crate::systemd::{unit_statuses, UnitStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+mod probes;
+
+pub use probes::ProbeConfig;
+
+/// Something `/health` can probe. Returns a short detail on success and the reason on failure.
+pub trait HealthCheck: Send + Sync {
+    fn check(&self) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + '_>>;
+}
+
+/// Health checks from the config file and how to combine them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    pub aggregation: Aggregation,
+    pub checks: Vec<CheckConfig>,
+    /// How often the checks and `systemd_units` are probed in the background for `/v1/health`
+    pub interval_secs: u64,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            aggregation: Aggregation::default(),
+            checks: Vec::new(),
+            interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    /// The worst single check decides
+    #[default]
+    Worst,
+    /// Weighted average of all checks, so one unimportant failure doesn't sink the status
+    Weighted,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub probe: ProbeConfig,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    /// Only used with `weighted` aggregation
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// What a failing probe counts as
+    #[serde(default = "default_severity")]
+    pub severity: HealthStatus,
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_severity() -> HealthStatus {
+    HealthStatus::Unhealthy
+}
+
+/// Run `probe` once, failing it when it takes longer than `timeout`
+pub async fn run_probe(probe: &dyn HealthCheck, timeout: Duration) -> (Result<String, String>, Duration) {
+    let start = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, probe.check()).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+    };
+    (outcome, start.elapsed())
+}
+
+/// Outcome of one check run
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: HealthStatus,
+    pub detail: String,
+    pub duration_ms: u128,
+    #[serde(skip)]
+    pub weight: f64,
+}
+
+/// Checks and systemd units from one background probe
+#[derive(Debug, Clone)]
+pub struct ProbeResults {
+    pub services: Vec<UnitStatus>,
+    pub checks: Vec<CheckResult>,
+    pub checked_at: DateTime<Utc>,
+}
+
+struct Check {
+    name: String,
+    timeout: Duration,
+    weight: f64,
+    severity: HealthStatus,
+    probe: Arc<dyn HealthCheck>,
+}
+
+/// The configured checks, run on demand by `/health` and in the background for `/v1/health`
+pub struct HealthChecks {
+    aggregation: Aggregation,
+    checks: Vec<Check>,
+    latest: RwLock<Option<Arc<ProbeResults>>>,
+}
+
+impl HealthChecks {
+    pub fn new(config: &HealthCheckConfig) -> Self {
+        let checks = config
+            .checks
+            .iter()
+            .map(|check| Check {
+                name: check.name.clone(),
+                timeout: Duration::from_secs(check.timeout_secs.max(1)),
+                weight: check.weight.max(0.0),
+                severity: check.severity,
+                probe: check.probe.build(),
+            })
+            .collect();
+
+        Self {
+            aggregation: config.aggregation,
+            checks,
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Checks and `units` together, the way the background loop probes them
+    pub async fn probe(&self, units: &[String]) -> ProbeResults {
+        let (services, checks) = tokio::join!(unit_statuses(units), self.run());
+        ProbeResults {
+            services,
+            checks,
+            checked_at: Utc::now(),
+        }
+    }
+
+    /// Results of the last background probe, `None` until the first one finishes
+    pub fn latest(&self) -> Option<Arc<ProbeResults>> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Probe the checks and `units` every `interval` for as long as the process runs
+    pub fn spawn(self: Arc<Self>, units: Vec<String>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let results = self.probe(&units).await;
+                *self.latest.write().unwrap() = Some(Arc::new(results));
+            }
+        });
+    }
+
+    /// Run every check at once, each bounded by its own timeout, in config order
+    pub async fn run(&self) -> Vec<CheckResult> {
+        let handles: Vec<_> = self
+            .checks
+            .iter()
+            .map(|check| {
+                let probe = check.probe.clone();
+                let timeout = check.timeout;
+                tokio::spawn(async move { run_probe(probe.as_ref(), timeout).await })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(handles.len());
+        for (check, handle) in self.checks.iter().zip(handles) {
+            let (outcome, elapsed) = handle
+                .await
+                .unwrap_or_else(|e| (Err(format!("check panicked: {}", e)), Duration::ZERO));
+            let (status, detail) = match outcome {
+                Ok(detail) => (HealthStatus::Healthy, detail),
+                Err(reason) => (check.severity, reason),
+            };
+            results.push(CheckResult {
+                name: check.name.clone(),
+                status,
+                detail,
+                duration_ms: elapsed.as_millis(),
+                weight: check.weight,
+            });
+        }
+        results
+    }
+
+    /// Combine check results into one status, `Healthy` when there are none
+    pub fn overall(&self, results: &[CheckResult]) -> HealthStatus {
+        match self.aggregation {
+            Aggregation::Worst => results.iter().map(|result| result.status).max().unwrap_or(HealthStatus::Healthy),
+            Aggregation::Weighted => {
+                let total_weight: f64 = results.iter().map(|result| result.weight).sum();
+                if total_weight <= 0.0 {
+                    return HealthStatus::Healthy;
+                }
+                let score = results
+                    .iter()
+                    .map(|result| result.weight * result.status as u8 as f64)
+                    .sum::<f64>()
+                    / total_weight;
+                // Healthy = 0, Degraded = 1, Unhealthy = 2, rounded to the nearest
+                if score < 0.5 {
+                    HealthStatus::Healthy
+                } else if score < 1.5 {
+                    HealthStatus::Degraded
+                } else {
+                    HealthStatus::Unhealthy
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks(aggregation: Aggregation) -> HealthChecks {
+        HealthChecks::new(&HealthCheckConfig {
+            aggregation,
+            ..Default::default()
+        })
+    }
+
+    fn result(status: HealthStatus, weight: f64) -> CheckResult {
+        CheckResult {
+            name: String::new(),
+            status,
+            detail: String::new(),
+            duration_ms: 0,
+            weight,
+        }
+    }
+
+    #[test]
+    fn no_results_are_healthy() {
+        assert_eq!(checks(Aggregation::Worst).overall(&[]), HealthStatus::Healthy);
+        assert_eq!(checks(Aggregation::Weighted).overall(&[]), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn worst_takes_the_worst_result() {
+        let results = [
+            result(HealthStatus::Healthy, 10.0),
+            result(HealthStatus::Unhealthy, 0.1),
+            result(HealthStatus::Degraded, 1.0),
+        ];
+        assert_eq!(checks(Aggregation::Worst).overall(&results), HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn weighted_averages_by_weight() {
+        let weighted = checks(Aggregation::Weighted);
+        // Unhealthy counts as 2, so a failure tips the average into degraded at a quarter of the weight
+        let results = [result(HealthStatus::Healthy, 3.0), result(HealthStatus::Unhealthy, 0.9)];
+        assert_eq!(weighted.overall(&results), HealthStatus::Healthy);
+        let results = [result(HealthStatus::Healthy, 3.0), result(HealthStatus::Unhealthy, 1.0)];
+        assert_eq!(weighted.overall(&results), HealthStatus::Degraded);
+        let results = [result(HealthStatus::Healthy, 1.0), result(HealthStatus::Unhealthy, 3.0)];
+        assert_eq!(weighted.overall(&results), HealthStatus::Unhealthy);
+        // Nothing carries weight, nothing to go by
+        let results = [result(HealthStatus::Unhealthy, 0.0)];
+        assert_eq!(weighted.overall(&results), HealthStatus::Healthy);
+    }
+
+    struct Sleepy(Duration);
+
+    impl HealthCheck for Sleepy {
+        fn check(&self) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + '_>> {
+            Box::pin(async move {
+                tokio::time::sleep(self.0).await;
+                Ok("done".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn probes_fail_when_they_run_past_their_timeout() {
+        let (outcome, _) = run_probe(&Sleepy(Duration::from_secs(5)), Duration::from_millis(10)).await;
+        assert!(outcome.is_err());
+        let (outcome, _) = run_probe(&Sleepy(Duration::ZERO), Duration::from_secs(1)).await;
+        assert_eq!(outcome.as_deref(), Ok("done"));
+    }
+}
@@ -0,0 +1,192 @@
+use super::HealthCheck;
+use serde::Deserialize;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+type CheckFuture<'a> = Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>>;
+
+/// The built-in probes, selected by `type` in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProbeConfig {
+    /// GET `url`, expecting `expected_status` and, if set, `expected_body` somewhere in the body
+    Http {
+        url: String,
+        #[serde(default = "default_status")]
+        expected_status: u16,
+        expected_body: Option<String>,
+    },
+    /// Open a TCP connection to `address` (`host:port`)
+    Tcp { address: String },
+    /// Resolve `host`, optionally requiring `expected_address` among the answers
+    Dns {
+        host: String,
+        expected_address: Option<IpAddr>,
+    },
+    /// `path` must exist and have been modified within `max_age_secs`
+    File { path: String, max_age_secs: u64 },
+    /// Run `command` with `args`, expecting `expected_exit_code`
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        expected_exit_code: i32,
+    },
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl ProbeConfig {
+    pub fn build(&self) -> Arc<dyn HealthCheck> {
+        match self.clone() {
+            ProbeConfig::Http { url, expected_status, expected_body } => Arc::new(HttpProbe {
+                client: reqwest::Client::new(),
+                url,
+                expected_status,
+                expected_body,
+            }),
+            ProbeConfig::Tcp { address } => Arc::new(TcpProbe { address }),
+            ProbeConfig::Dns { host, expected_address } => Arc::new(DnsProbe { host, expected_address }),
+            ProbeConfig::File { path, max_age_secs } => Arc::new(FileProbe {
+                path,
+                max_age: Duration::from_secs(max_age_secs),
+            }),
+            ProbeConfig::Command { command, args, expected_exit_code } => Arc::new(CommandProbe {
+                command,
+                args,
+                expected_exit_code,
+            }),
+        }
+    }
+}
+
+struct HttpProbe {
+    client: reqwest::Client,
+    url: String,
+    expected_status: u16,
+    expected_body: Option<String>,
+}
+
+impl HealthCheck for HttpProbe {
+    fn check(&self) -> CheckFuture<'_> {
+        Box::pin(async move {
+            let response = self.client.get(&self.url).send().await.map_err(|e| e.to_string())?;
+            let status = response.status().as_u16();
+            if status != self.expected_status {
+                return Err(format!("HTTP {}, expected {}", status, self.expected_status));
+            }
+
+            if let Some(expected_body) = &self.expected_body {
+                let body = response.text().await.map_err(|e| e.to_string())?;
+                if !body.contains(expected_body.as_str()) {
+                    return Err(format!("HTTP {}, body missing `{}`", status, expected_body));
+                }
+            }
+
+            Ok(format!("HTTP {}", status))
+        })
+    }
+}
+
+struct TcpProbe {
+    address: String,
+}
+
+impl HealthCheck for TcpProbe {
+    fn check(&self) -> CheckFuture<'_> {
+        Box::pin(async move {
+            let stream = tokio::net::TcpStream::connect(&self.address).await.map_err(|e| e.to_string())?;
+            let peer = stream.peer_addr().map_err(|e| e.to_string())?;
+            Ok(format!("connected to {}", peer))
+        })
+    }
+}
+
+struct DnsProbe {
+    host: String,
+    expected_address: Option<IpAddr>,
+}
+
+impl HealthCheck for DnsProbe {
+    fn check(&self) -> CheckFuture<'_> {
+        Box::pin(async move {
+            let addresses: Vec<IpAddr> = tokio::net::lookup_host((self.host.as_str(), 0))
+                .await
+                .map_err(|e| e.to_string())?
+                .map(|addr| addr.ip())
+                .collect();
+
+            match (self.expected_address, addresses.first()) {
+                (_, None) => Err("no addresses".to_string()),
+                (Some(expected), _) if !addresses.contains(&expected) => {
+                    Err(format!("resolved to {}, expected {}", addresses[0], expected))
+                }
+                (_, Some(first)) => Ok(format!("{} ({} addresses)", first, addresses.len())),
+            }
+        })
+    }
+}
+
+struct FileProbe {
+    path: String,
+    max_age: Duration,
+}
+
+impl HealthCheck for FileProbe {
+    fn check(&self) -> CheckFuture<'_> {
+        Box::pin(async move {
+            let modified = tokio::fs::metadata(&self.path)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| e.to_string())?;
+            // A timestamp in the future counts as fresh
+            let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+
+            let age_text = crate::utils::format_uptime(age.as_secs());
+            if age > self.max_age {
+                Err(format!("last modified {} ago", age_text))
+            } else {
+                Ok(format!("modified {} ago", age_text))
+            }
+        })
+    }
+}
+
+struct CommandProbe {
+    command: String,
+    args: Vec<String>,
+    expected_exit_code: i32,
+}
+
+impl HealthCheck for CommandProbe {
+    fn check(&self) -> CheckFuture<'_> {
+        Box::pin(async move {
+            // Dropped on timeout, which must not leave the child running
+            let output = tokio::process::Command::new(&self.command)
+                .args(&self.args)
+                .kill_on_drop(true)
+                .output()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let first_line = stdout.lines().chain(stderr.lines()).find(|line| !line.trim().is_empty());
+
+            match output.status.code() {
+                Some(code) if code == self.expected_exit_code => Ok(first_line.unwrap_or("ok").trim().to_string()),
+                Some(code) => Err(match first_line {
+                    Some(line) => format!("exit code {}: {}", code, line.trim()),
+                    None => format!("exit code {}", code),
+                }),
+                None => Err("killed by a signal".to_string()),
+            }
+        })
+    }
+}
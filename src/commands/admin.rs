@@ -1,6 +1,25 @@
 use crate::{Context, Error, Data};
 use poise::serenity_prelude as serenity;
-use crate::utils::format_uptime;
+use crate::charts::bar_chart;
+use crate::storage::{StatsFilter, StatsScope, StatsWindow};
+use crate::usage::{CommandTotals, Invocation};
+use crate::utils::{code_block_description, format_uptime, sparkline, truncate, MAX_EMBED_CHARS};
+use poise::ChoiceParameter;
+
+/// Invocation rows beyond this are left out of an export
+const EXPORT_MAX_ROWS: usize = 50_000;
+/// The files of one export stay within this, under Discord's upload limit
+const EXPORT_MAX_BYTES: usize = 8 * 1024 * 1024;
+/// Room for the CSV headers and the JSON envelope around the rows
+const EXPORT_OVERHEAD_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ExportFormat {
+    #[name = "csv"]
+    Csv,
+    #[name = "json"]
+    Json,
+}
 
 /// Show bot uptime
 #[poise::command(slash_command, prefix_command)]
@@ -25,50 +44,512 @@ pub async fn uptime(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// Show command usage statistics
-#[poise::command(slash_command, prefix_command)]
+/// Command usage and performance statistics
+#[poise::command(slash_command, prefix_command, subcommands("usage", "performance", "export"))]
 pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    // Only reachable as a bare `!stats`, slash commands always name a subcommand
+    show_usage(ctx, None, None).await
+}
+
+/// Show command usage statistics for a time window and scope
+#[poise::command(slash_command, prefix_command)]
+pub async fn usage(
+    ctx: Context<'_>,
+    #[description = "Time window (default: all)"] window: Option<StatsWindow>,
+    #[description = "Whose usage to count (default: this server, or your own in DMs)"] scope: Option<StatsScope>,
+) -> Result<(), Error> {
+    show_usage(ctx, window, scope).await
+}
+
+/// Show latency percentiles and error rates per command
+#[poise::command(slash_command, prefix_command, required_permissions = "MANAGE_GUILD")]
+pub async fn performance(
+    ctx: Context<'_>,
+    #[description = "Time window (default: 7d)"] window: Option<StatsWindow>,
+) -> Result<(), Error> {
     let data = ctx.data();
-    let command_count = data.command_count.lock().unwrap();
-    
-    let total_commands: u64 = command_count.values().sum();
-    let uptime_duration = chrono::Utc::now() - data.start_time;
-    let uptime_hours = uptime_duration.num_hours() as f64;
-    let commands_per_hour = if uptime_hours > 0.0 {
-        total_commands as f64 / uptime_hours
-    } else {
-        0.0
+    let window = window.unwrap_or(StatsWindow::Week);
+    let now = chrono::Utc::now();
+    // Owners see every server, everyone else the server they're in
+    let scope = if is_owner(ctx) { StatsScope::Global } else { default_scope(ctx) };
+    let filter = scope_filter(ctx, window, scope)?;
+    let slow_ms = data.config.slow_command_ms as f64;
+
+    let (commands, errors) = data
+        .storage
+        .read(move |storage| Ok((storage.performance(&filter)?, storage.recent_errors(&filter, 5)?)))
+        .await?;
+
+    let title = match window {
+        StatsWindow::All => "⏱️ Command Performance — all time".to_string(),
+        window => format!("⏱️ Command Performance — last {}", window.name()),
     };
-    
-    let mut stats_text = String::new();
-    if command_count.is_empty() {
-        stats_text.push_str("No commands have been used yet!");
+    let slow: Vec<_> = commands
+        .iter()
+        .filter(|command| command.p95.is_some_and(|p95| p95 >= slow_ms))
+        .collect();
+    let failing = commands.iter().any(|command| command.failures > 0);
+    let mut embed = serenity::CreateEmbed::new()
+        .title(title)
+        .color(if !slow.is_empty() { 0xffa500 } else if failing { 0xffd700 } else { 0x00ff00 })
+        .timestamp(now)
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Analytics"));
+
+    if commands.is_empty() {
+        embed = embed.description("No commands have been used in this window yet!");
+        ctx.send(poise::CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    }
+
+    let header = format!(
+        "{:<20} {:>6} {:>6} {:>7} {:>7} {:>7}\n",
+        "Command", "Uses", "Err", "p50", "p95", "p99"
+    );
+    let mut rows = Vec::with_capacity(commands.len());
+    for command in &commands {
+        let line = format!(
+            "{:<20} {:>6} {:>5.1}% {:>7} {:>7} {:>7}{}\n",
+            truncate(&command.command, 20),
+            command.uses,
+            command.error_rate(),
+            format_ms(command.p50),
+            format_ms(command.p95),
+            format_ms(command.p99),
+            if command.p95.is_some_and(|p95| p95 >= slow_ms) { " SLOW" } else { "" }
+        );
+        rows.push(line);
+    }
+    // The two fields below take up to 1024 characters each, plus the title and footer
+    embed = embed.description(code_block_description(&header, &rows, MAX_EMBED_CHARS - 2 * 1024 - 200));
+
+    if !slow.is_empty() {
+        let text = slow
+            .iter()
+            .map(|command| format!("🐢 **{}:** p95 {}", command.command, format_ms(command.p95)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field(
+            format!("Slow Commands (p95 ≥ {})", format_ms(Some(slow_ms))),
+            truncate(&text, 1024),
+            false,
+        );
+    }
+
+    if !errors.is_empty() {
+        let text = errors
+            .iter()
+            .map(|error| format!("<t:{}:R> **{}:** {}", error.at.timestamp(), error.command, truncate(&error.error, 100)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Recent Errors", truncate(&text, 1024), false);
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Export invocation records and per-command totals as a file
+#[poise::command(slash_command, prefix_command, required_permissions = "MANAGE_GUILD")]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "File format"] format: ExportFormat,
+    #[description = "Time window (default: 30d)"] window: Option<StatsWindow>,
+    #[description = "Whose usage to export (default: this server, or your own in DMs)"] scope: Option<StatsScope>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let window = window.unwrap_or(StatsWindow::Month);
+    let scope = scope.unwrap_or_else(|| default_scope(ctx));
+    let filter = match scope_filter(ctx, window, scope) {
+        Ok(filter) => filter,
+        Err(reason) => {
+            ctx.say(reason).await?;
+            return Ok(());
+        }
+    };
+    ctx.defer().await?;
+
+    // One more than the cap tells whether anything was left out
+    let (commands, mut invocations) = data
+        .storage
+        .read(move |storage| Ok((storage.command_totals(&filter)?, storage.invocations(&filter, EXPORT_MAX_ROWS + 1)?)))
+        .await?;
+    let mut truncated = invocations.len() > EXPORT_MAX_ROWS;
+    if truncated {
+        invocations.remove(0);
+    }
+
+    // Keep the latest invocations that fit next to the totals within the upload budget
+    let (totals_size, sizes): (usize, Vec<usize>) = match format {
+        ExportFormat::Csv => (
+            commands_csv(&commands).len(),
+            invocations.iter().map(|invocation| invocation_csv_row(invocation).len()).collect(),
+        ),
+        ExportFormat::Json => (
+            serde_json::to_vec(&commands_json(&commands))?.len(),
+            // Plus the comma between rows
+            invocations
+                .iter()
+                .map(|invocation| serde_json::to_vec(&invocation_json(invocation)).map_or(0, |row| row.len()) + 1)
+                .collect(),
+        ),
+    };
+    let fitting = latest_fitting(&sizes, EXPORT_MAX_BYTES.saturating_sub(totals_size + EXPORT_OVERHEAD_BYTES));
+    if fitting < invocations.len() {
+        truncated = true;
+        invocations.drain(..invocations.len() - fitting);
+    }
+
+    let now = chrono::Utc::now();
+    let stem = format!("stats-{}-{}-{}", scope.name(), window.name(), now.format("%Y%m%d-%H%M%S"));
+    let attachments = match format {
+        ExportFormat::Csv => vec![
+            serenity::CreateAttachment::bytes(commands_csv(&commands), format!("{}-commands.csv", stem)),
+            serenity::CreateAttachment::bytes(invocations_csv(&invocations), format!("{}-invocations.csv", stem)),
+        ],
+        ExportFormat::Json => {
+            let export = serde_json::json!({
+                "generated_at": now,
+                "window": window.name(),
+                "scope": scope.name(),
+                "truncated": truncated,
+                "commands": commands_json(&commands),
+                "invocations": invocations.iter().map(invocation_json).collect::<Vec<_>>(),
+            });
+            vec![serenity::CreateAttachment::bytes(serde_json::to_vec(&export)?, format!("{}.json", stem))]
+        }
+    };
+
+    let mut content = format!(
+        "📦 {} commands and {} invocations, {} scope, {}.",
+        commands.len(),
+        invocations.len(),
+        scope.name(),
+        match window {
+            StatsWindow::All => "all time".to_string(),
+            window => format!("last {}", window.name()),
+        }
+    );
+    if truncated {
+        content.push_str(&format!(
+            " Only the latest {} invocations fit in the export, pick a shorter window for the rest.",
+            invocations.len()
+        ));
+    }
+
+    let mut reply = poise::CreateReply::default().content(content);
+    for attachment in attachments {
+        reply = reply.attachment(attachment);
+    }
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+fn is_owner(ctx: Context<'_>) -> bool {
+    ctx.framework().options().owners.contains(&ctx.author().id)
+}
+
+/// `/stats` counts the current server, or in DMs everything for owners and your own usage for everyone else
+fn default_scope(ctx: Context<'_>) -> StatsScope {
+    if ctx.guild_id().is_some() {
+        StatsScope::Guild
+    } else if is_owner(ctx) {
+        StatsScope::Global
     } else {
-        // Sort commands by usage count
-        let mut sorted_commands: Vec<_> = command_count.iter().collect();
-        sorted_commands.sort_by(|a, b| b.1.cmp(a.1));
-        
-        for (command, count) in sorted_commands.iter().take(10) {
-            stats_text.push_str(&format!("**{}:** {} uses\n", command, count));
+        StatsScope::User
+    }
+}
+
+/// The invocations `scope` covers within `window`, or why it isn't available here.
+/// Only bot owners see invocations from other servers.
+fn scope_filter(ctx: Context<'_>, window: StatsWindow, scope: StatsScope) -> Result<StatsFilter, &'static str> {
+    let owner = is_owner(ctx);
+    let mut filter = StatsFilter {
+        since: window.duration().map(|duration| chrono::Utc::now() - duration),
+        ..Default::default()
+    };
+    match scope {
+        StatsScope::Global if !owner => return Err("Only bot owners can see statistics across all servers."),
+        StatsScope::Global => {}
+        StatsScope::Guild => {
+            filter.guild_id = Some(ctx.guild_id().ok_or("The `guild` scope only works inside a server.")?.get())
         }
+        StatsScope::User => filter.user_id = Some(ctx.author().id.get()),
+        StatsScope::Channel => filter.channel_id = Some(ctx.channel_id().get()),
     }
+    if !owner {
+        filter.guild_id = ctx.guild_id().map(|guild| guild.get());
+    }
+    Ok(filter)
+}
+
+async fn show_usage(ctx: Context<'_>, window: Option<StatsWindow>, scope: Option<StatsScope>) -> Result<(), Error> {
+    let data = ctx.data();
+    let window = window.unwrap_or(StatsWindow::All);
+    let now = chrono::Utc::now();
+    let scope = scope.unwrap_or_else(|| default_scope(ctx));
+    let filter = match scope_filter(ctx, window, scope) {
+        Ok(filter) => filter,
+        Err(reason) => {
+            ctx.say(reason).await?;
+            return Ok(());
+        }
+    };
+    let scope_label = match scope {
+        StatsScope::Global => "All servers",
+        StatsScope::Guild => "This server",
+        StatsScope::User => "Your usage",
+        StatsScope::Channel => "This channel",
+    };
+
+    // Already sorted by usage count
+    let (mut sorted_commands, first_invocation, unique_users, hours, top_users) = data
+        .storage
+        .read(move |storage| {
+            Ok((
+                storage.command_totals(&filter)?,
+                storage.first_invocation(&filter)?,
+                storage.unique_users(&filter)?,
+                storage.hour_of_day(&filter)?,
+                storage.top_users(&filter, 3)?,
+            ))
+        })
+        .await?;
     
-    let embed = serenity::CreateEmbed::new()
-        .title("📊 Bot Statistics")
-        .field("Total Commands", total_commands.to_string(), true)
-        .field("Commands/Hour", format!("{:.1}", commands_per_hour), true)
-        .field("Uptime", format_uptime(uptime_duration.num_seconds() as u64), true)
-        .field("Command Usage", if stats_text.is_empty() { "No data yet".to_string() } else { stats_text }, false)
+    let total_commands: u64 = sorted_commands.iter().map(|(_, totals)| totals.uses).sum();
+    let total_failures: u64 = sorted_commands.iter().map(|(_, totals)| totals.failures).sum();
+    let uptime_duration = now - data.start_time;
+    // Rates cover the window, or only the part of it since the first recorded command
+    let covered_hours = first_invocation
+        .map(|first| (now - first).num_seconds() as f64 / 3600.0)
+        .unwrap_or(0.0);
+    // Less than an hour of history would turn a handful of commands into a huge rate
+    let commands_per_hour = total_commands as f64 / covered_hours.max(1.0);
+    let commands_per_day = total_commands as f64 / (covered_hours / 24.0).max(1.0);
+    
+    sorted_commands.truncate(10);
+    
+    let mut stats_text = String::new();
+    for (command, totals) in &sorted_commands {
+        stats_text.push_str(&format!(
+            "**{}:** {} uses, {} failed · {} slash / {} prefix · avg {}ms\n",
+            command,
+            totals.uses,
+            totals.failures,
+            totals.slash,
+            totals.prefix,
+            totals.average_duration().as_millis()
+        ));
+    }
+
+    let title = match window {
+        StatsWindow::All => format!("📊 Bot Statistics — {}, all time", scope_label),
+        window => format!("📊 Bot Statistics — {}, last {}", scope_label, window.name()),
+    };
+    let mut embed = serenity::CreateEmbed::new()
+        .title(title)
         .color(0x00ced1)
-        .timestamp(chrono::Utc::now())
+        .timestamp(now)
         .footer(serenity::CreateEmbedFooter::new("2tarAPI | Analytics"));
 
-    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    if sorted_commands.is_empty() {
+        ctx.send(poise::CreateReply::default().embed(embed.description("No commands have been used in this window yet!"))).await?;
+        return Ok(());
+    }
+
+    embed = embed
+        .field("Total Commands", total_commands.to_string(), true)
+        .field("Failed", total_failures.to_string(), true)
+        .field("Unique Users", unique_users.to_string(), true)
+        .field("Commands/Hour", format!("{:.1}", commands_per_hour), true)
+        .field("Commands/Day", format!("{:.1}", commands_per_day), true)
+        .field("Bot Uptime", format_uptime(uptime_duration.num_seconds() as u64), true)
+        .field("Command Usage", truncate(&stats_text, 1024), false);
+
+    // Top users of the five busiest commands, pointless when only counting yourself
+    if scope != StatsScope::User {
+        let lines: Vec<String> = sorted_commands
+            .iter()
+            .take(5)
+            .filter_map(|(command, _)| {
+                let users = top_users.get(command)?;
+                let users: Vec<String> = users.iter().map(|(user, uses)| format!("<@{}> ({})", user, uses)).collect();
+                Some(format!("**{}:** {}", command, users.join(", ")))
+            })
+            .collect();
+        embed = embed.field("Top Users", truncate(&lines.join("\n"), 1024), false);
+    }
+
+    embed = embed.field("Busiest Hours (UTC)", hour_heatmap(&hours), false);
     
-    // Update command count
-    let command_name = ctx.command().name.clone();
-    let mut command_count = data.command_count.lock().unwrap();
-    *command_count.entry(command_name).or_insert(0) += 1;
+    // Bars follow the same order as the usage list above
+    let mut reply = poise::CreateReply::default();
+    let counts: Vec<u64> = sorted_commands.iter().map(|(_, totals)| totals.uses).collect();
+    if let Some(png) = bar_chart(&counts, 0x00ced1) {
+        embed = embed.image("attachment://stats.png");
+        reply = reply.attachment(serenity::CreateAttachment::bytes(png, "stats.png"));
+    }
+
+    ctx.send(reply.embed(embed)).await?;
     
     Ok(())
 }
+
+/// A 24-column sparkline of invocations per hour with an hour axis below it
+fn hour_heatmap(hours: &[u64; 24]) -> String {
+    let values: Vec<f64> = hours.iter().map(|count| *count as f64).collect();
+    let (busiest, count) = hours
+        .iter()
+        .enumerate()
+        .max_by_key(|(hour, count)| (**count, std::cmp::Reverse(*hour)))
+        .unwrap_or((0, &0));
+
+    format!(
+        "```{}\n00    06    12    18    ```Busiest: **{:02}:00–{:02}:00** with {} commands",
+        sparkline(&values, 24),
+        busiest,
+        (busiest + 1) % 24,
+        count
+    )
+}
+
+fn format_ms(ms: Option<f64>) -> String {
+    match ms {
+        None => "-".to_string(),
+        Some(ms) if ms < 1000.0 => format!("{:.0}ms", ms),
+        Some(ms) => format!("{:.1}s", ms / 1000.0),
+    }
+}
+
+fn commands_csv(commands: &[(String, CommandTotals)]) -> Vec<u8> {
+    let mut out = String::from("command,uses,failures,slash,prefix,avg_duration_ms\n");
+    for (command, totals) in commands {
+        out.push_str(&format!(
+            "{},{},{},{},{},{:.1}\n",
+            csv_field(command),
+            totals.uses,
+            totals.failures,
+            totals.slash,
+            totals.prefix,
+            totals.average_duration().as_secs_f64() * 1000.0
+        ));
+    }
+    out.into_bytes()
+}
+
+fn invocations_csv(invocations: &[Invocation]) -> Vec<u8> {
+    let mut out = String::from("at,command,kind,success,error,duration_ms,guild_id,channel_id,user_id\n");
+    for invocation in invocations {
+        out.push_str(&invocation_csv_row(invocation));
+    }
+    out.into_bytes()
+}
+
+fn invocation_csv_row(invocation: &Invocation) -> String {
+    let id = |id: Option<u64>| id.map(|id| id.to_string()).unwrap_or_default();
+    format!(
+        "{},{},{},{},{},{:.1},{},{},{}\n",
+        invocation.at.to_rfc3339(),
+        csv_field(&invocation.command),
+        invocation.kind.as_str(),
+        invocation.success,
+        csv_field(invocation.error.as_deref().unwrap_or("")),
+        invocation.duration.as_secs_f64() * 1000.0,
+        id(invocation.guild_id),
+        id(invocation.channel_id),
+        invocation.user_id
+    )
+}
+
+fn commands_json(commands: &[(String, CommandTotals)]) -> serde_json::Value {
+    commands
+        .iter()
+        .map(|(command, totals)| {
+            serde_json::json!({
+                "command": command,
+                "uses": totals.uses,
+                "failures": totals.failures,
+                "slash": totals.slash,
+                "prefix": totals.prefix,
+                "avg_duration_ms": totals.average_duration().as_secs_f64() * 1000.0,
+            })
+        })
+        .collect()
+}
+
+fn invocation_json(invocation: &Invocation) -> serde_json::Value {
+    serde_json::json!({
+        "at": invocation.at,
+        "command": invocation.command,
+        "kind": invocation.kind.as_str(),
+        "success": invocation.success,
+        "error": invocation.error,
+        "duration_ms": invocation.duration.as_secs_f64() * 1000.0,
+        // Snowflakes don't fit in a JavaScript number, so they go out as strings
+        "guild_id": invocation.guild_id.map(|id| id.to_string()),
+        "channel_id": invocation.channel_id.map(|id| id.to_string()),
+        "user_id": invocation.user_id.to_string(),
+    })
+}
+
+/// How many of the last `sizes` add up to at most `budget`
+fn latest_fitting(sizes: &[usize], budget: usize) -> usize {
+    let mut total = 0;
+    sizes
+        .iter()
+        .rev()
+        .take_while(|size| {
+            total += **size;
+            total <= budget
+        })
+        .count()
+}
+
+/// Quote a CSV text field when it contains a separator, quote or line break.
+/// Text that a spreadsheet would run as a formula gets a leading `'` to keep it text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_fitting_keeps_the_newest_rows_within_budget() {
+        assert_eq!(latest_fitting(&[5, 5, 5], 15), 3);
+        assert_eq!(latest_fitting(&[5, 5, 5], 14), 2);
+        assert_eq!(latest_fitting(&[1, 1, 20], 10), 0);
+        assert_eq!(latest_fitting(&[], 10), 0);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("ping"), "ping");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn csv_fields_never_start_a_formula() {
+        assert_eq!(csv_field("=HYPERLINK(\"http://example.com\")"), "\"'=HYPERLINK(\"\"http://example.com\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\tcell"), "'\tcell");
+        assert_eq!(csv_field("a = b"), "a = b");
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{Context, Error};
+use crate::commands::health::{disk_status, disk_status_label, HealthStatus};
+use crate::utils::{format_bytes, MAX_EMBED_FIELDS};
+use poise::serenity_prelude as serenity;
+
+/// List every mounted disk with its usage and status
+#[poise::command(slash_command, prefix_command)]
+pub async fn disks(ctx: Context<'_>) -> Result<(), Error> {
+    let snapshot = ctx.data().sampler.snapshot();
+    let config = &ctx.data().config;
+    
+    let mut disks: Vec<_> = snapshot.disks.iter().collect();
+    disks.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    
+    let mut worst = HealthStatus::Healthy;
+    let mut embed = serenity::CreateEmbed::new()
+        .title("💽 Disk Usage")
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Storage Monitor"));
+    
+    if disks.is_empty() {
+        embed = embed.description("No disks found on this host.");
+    }
+    
+    for disk in disks.iter().take(MAX_EMBED_FIELDS) {
+        let status = disk_status(disk, config);
+        worst = worst.max(status);
+        
+        let inodes = match disk.inode_usage_percent() {
+            Some(percent) => format!("{:.1}% used", percent),
+            None => "n/a".to_string(),
+        };
+        
+        embed = embed.field(
+            format!("{} {}", disk_status_label(status), disk.mount_point),
+            format!(
+                "**Device:** `{}` ({})\n**Used:** {} / {} ({:.1}%)\n**Free:** {}\n**Inodes:** {}",
+                disk.name,
+                disk.file_system,
+                format_bytes(disk.total - disk.available),
+                format_bytes(disk.total),
+                disk.usage_percent(),
+                format_bytes(disk.available),
+                inodes
+            ),
+            true,
+        );
+    }
+    if disks.len() > MAX_EMBED_FIELDS {
+        embed = embed.description(format!("Showing {} of {} mounts.", MAX_EMBED_FIELDS, disks.len()));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed.color(worst.color()))).await?;
+    
+    Ok(())
+}
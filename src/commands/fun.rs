@@ -1,4 +1,5 @@
 use crate::{Context, Error};
+use crate::charts::histogram;
 use poise::serenity_prelude as serenity;
 use rand::Rng;
 
@@ -77,17 +78,14 @@ pub async fn roll(
     let sides = sides.unwrap_or(6).max(2).min(1000); // Between 2-1000 sides
     let count = count.unwrap_or(1).max(1).min(10);   // Between 1-10 dice
     
-    let mut rng = rand::thread_rng();
-    let mut results = Vec::new();
-    let mut total = 0;
-    
-    for _ in 0..count {
-        let roll = rng.gen_range(1..=sides);
-        results.push(roll);
-        total += roll;
-    }
+    // `ThreadRng` isn't `Send`, so it must be gone before the first `.await`
+    let results: Vec<u32> = {
+        let mut rng = rand::thread_rng();
+        (0..count).map(|_| rng.gen_range(1..=sides)).collect()
+    };
+    let total: u32 = results.iter().sum();
     
-    let embed = serenity::CreateEmbed::new()
+    let mut embed = serenity::CreateEmbed::new()
         .title("🎲 Dice Roll Results")
         .field("Dice", format!("{}d{}", count, sides), true)
         .field("Results", results.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", "), true)
@@ -95,8 +93,17 @@ pub async fn roll(
         .color(0xff6b6b)
         .timestamp(chrono::Utc::now())
         .footer(serenity::CreateEmbedFooter::new("2tarAPI | Random Generator"));
+    
+    // A single die has nothing to compare against, only chart multiple rolls
+    let mut reply = poise::CreateReply::default();
+    if count > 1 {
+        if let Some(png) = histogram(&results, sides, 0xff6b6b) {
+            embed = embed.image("attachment://roll.png");
+            reply = reply.attachment(serenity::CreateAttachment::bytes(png, "roll.png"));
+        }
+    }
 
-    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    ctx.send(reply.embed(embed)).await?;
     
     Ok(())
 }
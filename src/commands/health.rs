@@ -1,74 +1,302 @@
 use crate::{Context, Error};
+use crate::cgroup::ContainerInfo;
+use crate::checks::CheckResult;
+use crate::config::Config;
+use crate::latency::dispatch_delay;
+use crate::scoring::{self, HealthInputs};
+use crate::systemd::{unit_statuses, UnitStatus};
+use crate::utils::{join_lines, DiskUsage, SensorReading};
 use poise::serenity_prelude as serenity;
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Overall health verdict shared by `/health` and the HTTP API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "🟢 Healthy",
+            HealthStatus::Degraded => "🟡 Degraded",
+            HealthStatus::Unhealthy => "🔴 Unhealthy",
+        }
+    }
+
+    pub fn emoji(self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "🟢",
+            HealthStatus::Degraded => "🟡",
+            HealthStatus::Unhealthy => "🔴",
+        }
+    }
+
+    pub fn color(self) -> u32 {
+        match self {
+            HealthStatus::Healthy => 0x00ff00,
+            HealthStatus::Degraded => 0xffa500,
+            HealthStatus::Unhealthy => 0xff0000,
+        }
+    }
+}
 
 /// Check API health and response time
 #[poise::command(slash_command, prefix_command)]
 pub async fn health(ctx: Context<'_>) -> Result<(), Error> {
     let start = Instant::now();
+    let dispatch_delay = dispatch_delay(ctx.id());
     
-    // Defer the response to measure actual response time
     ctx.defer().await?;
     
-    let response_time = start.elapsed();
+    // Time a real REST request rather than the defer, which for prefix commands only starts typing
+    let (rest_latency, units, check_results) = tokio::join!(
+        ctx.data().latency.measure_rest(ctx.http()),
+        unit_statuses(&ctx.data().config.systemd_units),
+        ctx.data().checks.run()
+    );
     
-    // Test various system components
-    let discord_latency = ctx.ping().await;
-    let memory_usage = get_memory_usage();
-    let disk_health = check_disk_health().await;
+    let snapshot = ctx.data().sampler.snapshot();
+    let memory_usage = snapshot.memory().usage_percent();
+    let gateway_latency = ctx.data().gateway.latency();
+    let active_alerts = ctx.data().alerts.active();
+    let report = scoring::evaluate(
+        &ctx.data().config,
+        &HealthInputs {
+            snapshot: &snapshot,
+            gateway_latency,
+            rest_latency,
+            services: &units,
+            checks: &check_results,
+            checks_status: ctx.data().checks.overall(&check_results),
+            alerts: &active_alerts,
+        },
+    );
     
-    let health_status = if response_time.as_millis() < 500 && memory_usage < 80.0 {
-        ("🟢 Healthy", 0x00ff00)
-    } else if response_time.as_millis() < 1000 && memory_usage < 90.0 {
-        ("🟡 Degraded", 0xffa500)
+    let alerts_text = if active_alerts.is_empty() {
+        "✅ None firing".to_string()
     } else {
-        ("🔴 Unhealthy", 0xff0000)
+        let lines: Vec<String> = active_alerts
+            .iter()
+            .map(|alert| format!("🚨 **{}** since <t:{}:R>", alert.name, alert.since.timestamp()))
+            .collect();
+        join_lines(&lines, 1024)
+    };
+    let format_latency = |latency: Option<Duration>| match latency {
+        Some(latency) => format!("{}ms", latency.as_millis()),
+        None => "n/a".to_string(),
+    };
+    let heartbeat = match ctx.data().gateway.heartbeat_summary() {
+        Some((summary, count)) => format!(
+            "{} (avg {:.0}ms, max {:.0}ms over {} beats)",
+            format_latency(gateway_latency),
+            summary.avg,
+            summary.max,
+            count
+        ),
+        None => format_latency(gateway_latency),
+    };
+    let latency_text = |reply: &str| {
+        format!(
+            "**Gateway heartbeat:** {}\n**REST round-trip:** {}\n**Snowflake → handler:** {}ms\n**Command → reply:** {}",
+            heartbeat,
+            format_latency(rest_latency),
+            dispatch_delay.as_millis(),
+            reply
+        )
     };
     
-    let embed = serenity::CreateEmbed::new()
+    let mut embed = serenity::CreateEmbed::new()
         .title("⚡ API Health Check")
-        .color(health_status.1)
-        .field("Status", health_status.0, true)
-        .field("Response Time", format!("{}ms", response_time.as_millis()), true)
-        .field("Discord Latency", format!("{}ms", discord_latency.as_millis()), true)
-        .field("Memory Usage", format!("{:.1}%", memory_usage), true)
-        .field("Disk Status", disk_health, true)
+        .color(report.status.color())
+        .field("Status", format!("{} ({:.0}/100)", report.status.label(), report.score), true)
+        .field(
+            "Memory Usage",
+            if snapshot.memory_limited() {
+                format!("{:.1}% of cgroup limit", memory_usage)
+            } else {
+                format!("{:.1}%", memory_usage)
+            },
+            true,
+        )
+        .field("CPU Usage", format!("{:.1}%", snapshot.cpu_usage()), true)
+        .field("Disk Status", check_disk_health(&snapshot.disks, &ctx.data().config), true)
+        .field("Temperatures", check_sensor_health(&snapshot.sensors), true)
         .field("API Version", "v2.0.0", true)
+        .field("Container", container_summary(&snapshot.container), true)
+        .field("Services", check_service_health(&units), true)
+        .field("Alerts", alerts_text, false)
         .timestamp(chrono::Utc::now())
         .footer(serenity::CreateEmbedFooter::new("2tarAPI Health Monitor"));
+    
+    let downgrades = report.downgrades();
+    if !downgrades.is_empty() {
+        let reasons = downgrades
+            .iter()
+            .map(|component| {
+                format!(
+                    "{} **{}**: {} (-{:.0} pts)",
+                    component.status.emoji(),
+                    component.name,
+                    component.detail,
+                    component.impact
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Downgraded By", reasons, false);
+    }
+    if !check_results.is_empty() {
+        embed = embed.field("Checks", format_check_results(&check_results), false);
+    }
 
-    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    // The reply time is only known once Discord accepted the reply, so fill it in with an edit
+    let reply = ctx
+        .send(poise::CreateReply::default().embed(embed.clone().field("⏱️ Latency", latency_text("measuring…"), false)))
+        .await?;
+    let reply_time = start.elapsed();
+    ctx.data().latency.record_reply(reply_time);
+    reply
+        .edit(
+            ctx,
+            poise::CreateReply::default()
+                .embed(embed.field("⏱️ Latency", latency_text(&format!("{}ms", reply_time.as_millis())), false)),
+        )
+        .await?;
     
     Ok(())
 }
 
-fn get_memory_usage() -> f64 {
-    use sysinfo::{System, SystemExt};
-    let mut sys = System::new_all();
-    sys.refresh_memory();
+/// Used space across all disks, as one percentage
+pub fn combined_disk_usage(disks: &[DiskUsage]) -> Option<f64> {
+    if disks.is_empty() {
+        return None;
+    }
     
-    (sys.used_memory() as f64 / sys.total_memory() as f64) * 100.0
+    let total_space: u64 = disks.iter().map(|d| d.total).sum();
+    let available_space: u64 = disks.iter().map(|d| d.available).sum();
+    Some(((total_space - available_space) as f64 / total_space as f64) * 100.0)
 }
 
-async fn check_disk_health() -> String {
-    use sysinfo::{System, SystemExt, DiskExt};
-    let mut sys = System::new_all();
-    sys.refresh_disks();
+pub fn disk_status(disk: &DiskUsage, config: &Config) -> HealthStatus {
+    let thresholds = config.disk_thresholds_for(&disk.mount_point);
+    let usage_percent = disk.usage_percent().max(disk.inode_usage_percent().unwrap_or(0.0));
     
-    let disks = sys.disks();
-    if disks.is_empty() {
-        return "❓ Unknown".to_string();
+    if usage_percent < thresholds.warning {
+        HealthStatus::Healthy
+    } else if usage_percent < thresholds.critical {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Unhealthy
+    }
+}
+
+pub fn disk_status_label(status: HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Healthy => "🟢 Good",
+        HealthStatus::Degraded => "🟡 Warning",
+        HealthStatus::Unhealthy => "🔴 Critical",
+    }
+}
+
+/// Status of the fullest disk relative to its thresholds, naming it when it isn't good
+pub fn check_disk_health(disks: &[DiskUsage], config: &Config) -> String {
+    let worst = disks
+        .iter()
+        .map(|disk| (disk_status(disk, config), disk))
+        .max_by_key(|(status, _)| *status);
+    
+    match worst {
+        None => "❓ Unknown".to_string(),
+        Some((HealthStatus::Healthy, _)) => disk_status_label(HealthStatus::Healthy).to_string(),
+        Some((status, disk)) => format!("{} ({})", disk_status_label(status), disk.mount_point),
+    }
+}
+
+/// Critical or within 10°C of it; sensors without a critical point use 80/90°C
+pub fn sensor_status(sensor: &SensorReading) -> HealthStatus {
+    let (warning, critical) = match sensor.critical {
+        Some(critical) => (critical - 10.0, critical),
+        None => (80.0, 90.0),
+    };
+    
+    if sensor.temperature >= critical {
+        HealthStatus::Unhealthy
+    } else if sensor.temperature >= warning {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    }
+}
+
+pub fn worst_sensor_status(sensors: &[SensorReading]) -> HealthStatus {
+    sensors.iter().map(sensor_status).max().unwrap_or(HealthStatus::Healthy)
+}
+
+/// Status of the hottest sensor relative to its limits, naming it when it isn't good
+pub fn check_sensor_health(sensors: &[SensorReading]) -> String {
+    let worst = sensors
+        .iter()
+        .map(|sensor| (sensor_status(sensor), sensor))
+        .max_by_key(|(status, _)| *status);
+    
+    match worst {
+        None => "➖ No sensors".to_string(),
+        Some((HealthStatus::Healthy, _)) => "🟢 Normal".to_string(),
+        Some((HealthStatus::Degraded, sensor)) => format!("🟡 Hot ({} {:.0}°C)", sensor.label, sensor.temperature),
+        Some((HealthStatus::Unhealthy, sensor)) => format!("🔴 Critical ({} {:.0}°C)", sensor.label, sensor.temperature),
+    }
+}
+
+pub fn container_summary(container: &ContainerInfo) -> String {
+    if !container.containerized {
+        return "➖ Bare metal / VM".to_string();
     }
     
-    let total_space: u64 = disks.iter().map(|d| d.total_space()).sum();
-    let available_space: u64 = disks.iter().map(|d| d.available_space()).sum();
-    let usage_percent = ((total_space - available_space) as f64 / total_space as f64) * 100.0;
+    match &container.runtime {
+        Some(runtime) => format!("📦 {}", runtime),
+        None => "📦 Containerized".to_string(),
+    }
+}
+
+pub fn worst_unit_status(units: &[UnitStatus]) -> HealthStatus {
+    units.iter().map(|unit| unit.health()).max().unwrap_or(HealthStatus::Healthy)
+}
+
+/// How many watched units are up, naming the ones that aren't
+pub fn check_service_health(units: &[UnitStatus]) -> String {
+    if units.is_empty() {
+        return "➖ None watched".to_string();
+    }
+    
+    let down: Vec<&str> = units
+        .iter()
+        .filter(|unit| unit.health() != HealthStatus::Healthy)
+        .map(|unit| unit.name.as_str())
+        .collect();
+    let status = worst_unit_status(units);
     
-    if usage_percent < 80.0 {
-        "🟢 Good"
-    } else if usage_percent < 90.0 {
-        "🟡 Warning"
+    if down.is_empty() {
+        format!("{} {}/{} active", status.emoji(), units.len(), units.len())
     } else {
-        "🔴 Critical"
-    }.to_string()
+        format!("{} {}/{} active, down: {}", status.emoji(), units.len() - down.len(), units.len(), down.join(", "))
+    }
+}
+
+/// One line per check with its result and duration, within Discord's 1024 character field limit
+pub fn format_check_results(results: &[CheckResult]) -> String {
+    let mut text = String::new();
+    for (index, result) in results.iter().enumerate() {
+        let line = format!("{} **{}** {} ({}ms)\n", result.status.emoji(), result.name, result.detail, result.duration_ms);
+        if text.len() + line.len() > 1000 {
+            text.push_str(&format!("… and {} more", results.len() - index));
+            break;
+        }
+        text.push_str(&line);
+    }
+    text
 }
@@ -0,0 +1,44 @@
+use crate::{Context, Error};
+use crate::charts::line_chart;
+use crate::history::{Metric, Window};
+use poise::ChoiceParameter;
+use crate::utils::{sparkline, summarize};
+use poise::serenity_prelude as serenity;
+
+/// Show how a host metric has changed over time
+#[poise::command(slash_command, prefix_command)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "Metric to show"] metric: Metric,
+    #[description = "Time window (default: 1h)"] window: Option<Window>,
+) -> Result<(), Error> {
+    let window = window.unwrap_or(Window::Hour);
+    let values = ctx.data().history.series(metric, window);
+    
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("📈 {} — last {}", metric.label(), window.name()))
+        .color(0x1e90ff)
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Metric History"));
+    
+    let mut reply = poise::CreateReply::default();
+    if let Some(png) = line_chart(&values, 0x1e90ff) {
+        embed = embed.image("attachment://history.png");
+        reply = reply.attachment(serenity::CreateAttachment::bytes(png, "history.png"));
+    }
+    
+    embed = match summarize(&values) {
+        Some(summary) => embed
+            .description(format!("```{}```", sparkline(&values, 40)))
+            .field("Min", metric.format(summary.min), true)
+            .field("Avg", metric.format(summary.avg), true)
+            .field("Max", metric.format(summary.max), true)
+            .field("p95", metric.format(summary.p95), true)
+            .field("Samples", values.len().to_string(), true),
+        None => embed.description("No samples recorded in this window yet, check back in a minute!"),
+    };
+
+    ctx.send(reply.embed(embed)).await?;
+    
+    Ok(())
+}
@@ -1,64 +1,158 @@
 use crate::{Context, Error};
-use crate::utils::{get_system_info, get_public_ip, get_local_ip, format_bytes, format_uptime};
+use crate::sampler::Snapshot;
+use crate::utils::{get_local_ip, format_bytes, format_uptime};
+use crate::visibility::{SensitiveField, View};
 use poise::serenity_prelude as serenity;
 
 /// Get comprehensive server information
 #[poise::command(slash_command, prefix_command)]
-pub async fn server_info(ctx: Context<'_>) -> Result<(), Error> {
-    ctx.defer().await?;
+pub async fn server_info(
+    ctx: Context<'_>,
+    #[description = "Also list the usage of every CPU core (default: false)"] per_core: Option<bool>,
+) -> Result<(), Error> {
+    // Admins get the full view, which must then only be visible to them
+    let view = View::for_context(ctx);
+    if view.ephemeral() {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
     
-    let system_info = get_system_info();
-    let public_ip = get_public_ip().await.unwrap_or_else(|_| "Unknown".to_string());
-    let local_ip = get_local_ip().unwrap_or_else(|| "Unknown".to_string());
+    let snapshot = ctx.data().sampler.snapshot();
+    let system_info = &snapshot.system;
+    let public_ip = ctx.data().public_ip.get().await;
     
-    let embed = serenity::CreateEmbed::new()
+    let line = |label: &str, field: SensitiveField, value: Option<String>| {
+        let value = value.unwrap_or_else(|| "Unknown".to_string());
+        view.show(field, &value).map(|value| format!("**{}:** {}", label, value))
+    };
+    let network_lines: Vec<String> = [
+        line("Public IPv4", SensitiveField::PublicIp, public_ip.v4.map(|ip| ip.to_string())),
+        line("Public IPv6", SensitiveField::PublicIp, public_ip.v6.map(|ip| ip.to_string())),
+        line("Local IP", SensitiveField::LocalIp, get_local_ip()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let system_lines: Vec<String> = [
+        line("OS", SensitiveField::OsVersion, Some(system_info.os_version.clone())),
+        line("Kernel", SensitiveField::KernelVersion, Some(system_info.kernel_version.clone())),
+        Some(format!("**Uptime:** {}", format_uptime(system_info.uptime))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    
+    // Inside a container the cgroup limits are what actually constrain the bot
+    let memory = snapshot.memory();
+    let memory_title = if snapshot.memory_limited() { "💾 Memory (cgroup limit)" } else { "💾 Memory" };
+    
+    let cpu = &system_info.cpu;
+    let cores = match cpu.physical_cores {
+        Some(physical) => format!("{} physical / {} logical", physical, cpu.logical_cores),
+        None => format!("{} logical", cpu.logical_cores),
+    };
+    
+    let mut embed = serenity::CreateEmbed::new()
         .title("🖥️ Server Information")
         .color(0x00ff00)
         .field(
-            "💾 Memory",
+            memory_title,
             format!(
                 "**Total:** {}\n**Used:** {}\n**Available:** {}\n**Usage:** {:.1}%",
-                format_bytes(system_info.memory.total),
-                format_bytes(system_info.memory.used),
-                format_bytes(system_info.memory.available),
-                (system_info.memory.used as f64 / system_info.memory.total as f64) * 100.0
+                format_bytes(memory.total),
+                format_bytes(memory.used),
+                format_bytes(memory.available),
+                memory.usage_percent()
             ),
             true,
         )
         .field(
             "🔧 CPU",
             format!(
-                "**Model:** {}\n**Frequency:** {} MHz\n**Usage:** {:.1}%",
-                system_info.cpu.brand,
-                system_info.cpu.frequency,
-                system_info.cpu.usage
-            ),
-            true,
-        )
-        .field(
-            "🌐 Network",
-            format!(
-                "**Public IP:** {}\n**Local IP:** {}",
-                public_ip,
-                local_ip
+                "**Model:** {}\n**Cores:** {}\n**Frequency:** {} MHz\n**Usage:** {:.1}%\n**Load:** {:.2} / {:.2} / {:.2}",
+                cpu.brand,
+                cores,
+                cpu.frequency,
+                cpu.usage,
+                cpu.load_average.one,
+                cpu.load_average.five,
+                cpu.load_average.fifteen
             ),
             true,
-        )
-        .field(
-            "⚙️ System",
-            format!(
-                "**OS:** {}\n**Kernel:** {}\n**Uptime:** {}",
-                system_info.os_version,
-                system_info.kernel_version,
-                format_uptime(system_info.uptime)
-            ),
-            false,
-        )
+        );
+    if !network_lines.is_empty() {
+        embed = embed.field("🌐 Network", network_lines.join("\n"), true);
+    }
+    embed = embed
+        .field("⚙️ System", system_lines.join("\n"), false)
         .thumbnail("https://cdn.discordapp.com/emojis/1234567890123456789.png") // You can replace with your bot's icon
         .timestamp(chrono::Utc::now())
         .footer(serenity::CreateEmbedFooter::new("2tarAPI | System Monitor"));
+    
+    if view.redacts_any(&[
+        SensitiveField::PublicIp,
+        SensitiveField::LocalIp,
+        SensitiveField::KernelVersion,
+        SensitiveField::OsVersion,
+    ]) {
+        embed = embed.description("Some host details are redacted. Server admins can run `/server_info` for the full view.");
+    }
+    
+    if snapshot.container.containerized || snapshot.container.cpu_quota.is_some() {
+        embed = embed.field("📦 Container", format_container(&snapshot), false);
+    }
+    
+    if per_core.unwrap_or(false) {
+        embed = embed.field("🧮 Per-Core Usage", format_per_core(&cpu.per_core_usage), false);
+    }
 
-    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(view.ephemeral())).await?;
     
     Ok(())
 }
+
+fn format_container(snapshot: &Snapshot) -> String {
+    let container = &snapshot.container;
+    let mut lines = vec![format!(
+        "**Runtime:** {}\n**cgroup:** {}",
+        container.runtime.as_deref().unwrap_or("unknown"),
+        container.cgroup_version.map(|version| format!("v{}", version)).unwrap_or_else(|| "n/a".to_string())
+    )];
+    
+    if let Some(quota) = container.cpu_quota {
+        lines.push(format!("**CPU Quota:** {:.2} cores ({:.1}% used)", quota, snapshot.cpu_usage()));
+    }
+    match (container.pids_current, container.pids_limit) {
+        (Some(current), Some(limit)) => lines.push(format!("**PIDs:** {} / {}", current, limit)),
+        (Some(current), None) => lines.push(format!("**PIDs:** {} (no limit)", current)),
+        _ => {}
+    }
+    
+    lines.join("\n")
+}
+
+/// Lay per-core usage out four to a line, within Discord's 1024 character field limit
+fn format_per_core(per_core_usage: &[f32]) -> String {
+    if per_core_usage.is_empty() {
+        return "No per-core data available".to_string();
+    }
+    
+    let mut text = String::new();
+    for (row, chunk) in per_core_usage.chunks(4).enumerate() {
+        let line = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, usage)| format!("`{:>3}` {:>5.1}%", row * 4 + i, usage))
+            .collect::<Vec<_>>()
+            .join("  ");
+        
+        if text.len() + line.len() + 1 > 1000 {
+            text.push_str(&format!("… and {} more", per_core_usage.len() - row * 4));
+            break;
+        }
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
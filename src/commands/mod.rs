@@ -1,4 +1,11 @@
 pub mod info;
 pub mod health;
+pub mod disks;
+pub mod top;
+pub mod network;
+pub mod monitors;
+pub mod sensors;
+pub mod services;
 pub mod fun;
 pub mod admin;
+pub mod history;
@@ -0,0 +1,87 @@
+use crate::{Context, Error};
+use crate::utils::{code_block_description, format_uptime, truncate, MAX_EMBED_DESCRIPTION};
+use poise::serenity_prelude as serenity;
+
+/// Show the uptime monitors with their availability and recent incidents
+#[poise::command(slash_command, prefix_command)]
+pub async fn monitors(ctx: Context<'_>) -> Result<(), Error> {
+    let monitors = &ctx.data().monitors;
+    let statuses = monitors.statuses();
+    
+    let down = statuses.iter().filter(|status| status.up == Some(false)).count();
+    let color = match down {
+        0 => 0x00ff00,
+        n if n < statuses.len() => 0xffa500,
+        _ => 0xff0000,
+    };
+    
+    let mut embed = serenity::CreateEmbed::new()
+        .title("📡 Uptime Monitors")
+        .color(color)
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Uptime Monitor"));
+    
+    if monitors.is_empty() {
+        embed = embed.description("No monitors are configured. Add targets under `monitors` in the config file.");
+        ctx.send(poise::CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    }
+    
+    let availability = |value: Option<f64>| match value {
+        Some(value) => format!("{:.2}%", value),
+        None => "-".to_string(),
+    };
+    let header = format!("{:<18} {:<7} {:>8} {:>8} {:>8} {:>7}\n", "Monitor", "Status", "24h", "7d", "30d", "Resp");
+    let mut rows = Vec::with_capacity(statuses.len());
+    for status in &statuses {
+        let state = match status.up {
+            Some(true) => "UP",
+            Some(false) => "DOWN",
+            None => "PENDING",
+        };
+        let response = status.response_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string());
+        let line = format!(
+            "{:<18} {:<7} {:>8} {:>8} {:>8} {:>7}\n",
+            truncate(&status.name, 18),
+            state,
+            availability(status.availability_24h),
+            availability(status.availability_7d),
+            availability(status.availability_30d),
+            response
+        );
+        rows.push(line);
+    }
+    embed = embed.description(code_block_description(&header, &rows, MAX_EMBED_DESCRIPTION));
+    
+    let incidents = monitors.incidents(5);
+    if !incidents.is_empty() {
+        let now = chrono::Utc::now();
+        let text = incidents
+            .iter()
+            .map(|incident| {
+                let duration = incident.ended_at.unwrap_or(now) - incident.started_at;
+                let duration = format_uptime(duration.num_seconds().max(0) as u64);
+                match incident.ended_at {
+                    Some(_) => format!(
+                        "🟢 **{}** <t:{}:R>, down for {}",
+                        incident.monitor,
+                        incident.started_at.timestamp(),
+                        duration
+                    ),
+                    None => format!(
+                        "🔴 **{}** down for {}: {}",
+                        incident.monitor,
+                        duration,
+                        truncate(&incident.reason, 80)
+                    ),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Recent Incidents", text, false);
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    
+    Ok(())
+}
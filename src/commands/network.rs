@@ -0,0 +1,100 @@
+use crate::{Context, Error};
+use crate::utils::{format_bytes, FieldBudget};
+use crate::utils::network::get_tcp_states;
+use crate::visibility::{SensitiveField, View};
+use poise::serenity_prelude as serenity;
+
+/// List network interfaces with their addresses, traffic and TCP connection counts
+#[poise::command(slash_command, prefix_command)]
+pub async fn network(ctx: Context<'_>) -> Result<(), Error> {
+    let view = View::for_context(ctx);
+    let snapshot = ctx.data().sampler.snapshot();
+    let tcp_states = get_tcp_states();
+    
+    let mut embed = serenity::CreateEmbed::new()
+        .title("🌐 Network Interfaces")
+        .color(0x1abc9c)
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Network Monitor"));
+    
+    if snapshot.networks.is_empty() {
+        embed = embed.description("No network interfaces found on this host.");
+    }
+    
+    // Hold back room for the title, description, footer and the TCP summary field
+    let mut budget = FieldBudget::new(1000, 1);
+    for interface in &snapshot.networks {
+        let show = |ip: &std::net::IpAddr| view.show(SensitiveField::InterfaceAddresses, &ip.to_string());
+        let ipv4: Vec<String> = interface.addresses.iter().filter(|ip| ip.is_ipv4()).filter_map(show).collect();
+        let ipv6: Vec<String> = interface.addresses.iter().filter(|ip| ip.is_ipv6()).filter_map(show).collect();
+        let mac = interface.mac.as_deref().and_then(|mac| view.show(SensitiveField::InterfaceAddresses, mac));
+        // Hidden addresses leave out the MAC/IPv4/IPv6 lines entirely
+        let addresses = if view.hides(SensitiveField::InterfaceAddresses) {
+            String::new()
+        } else {
+            format!(
+                "**MAC:** {}\n**IPv4:** {}\n**IPv6:** {}\n",
+                mac.as_deref().unwrap_or("n/a"),
+                list_or_none(&ipv4),
+                list_or_none(&ipv6)
+            )
+        };
+        
+        let name = format!("🔌 {}", interface.name);
+        let value = format!(
+            "{}**RX:** {}/s — {} total, {} pkts, {} errs\n**TX:** {}/s — {} total, {} pkts, {} errs",
+            addresses,
+            format_bytes(interface.rx_rate as u64),
+            format_bytes(interface.rx_bytes),
+            interface.rx_packets,
+            interface.rx_errors,
+            format_bytes(interface.tx_rate as u64),
+            format_bytes(interface.tx_bytes),
+            interface.tx_packets,
+            interface.tx_errors
+        );
+        if budget.fits(&name, &value) {
+            embed = embed.field(name, value, false);
+        }
+    }
+    
+    let tcp_text = if tcp_states.is_empty() {
+        "Not available on this host".to_string()
+    } else {
+        tcp_states
+            .iter()
+            .map(|(state, count)| format!("**{}:** {}", state, count))
+            .collect::<Vec<_>>()
+            .join(" · ")
+    };
+    embed = embed.field("🔗 TCP Connections", tcp_text, false);
+
+    let mut notes = Vec::new();
+    if budget.skipped() > 0 {
+        notes.push(format!("+{} more interfaces that don't fit in one message.", budget.skipped()));
+    }
+    if view.redacts_any(&[SensitiveField::InterfaceAddresses]) {
+        notes.push("Addresses are redacted. Server admins can run `/network` for the full view.".to_string());
+    }
+    if !notes.is_empty() {
+        embed = embed.description(notes.join("\n"));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(view.ephemeral())).await?;
+    
+    Ok(())
+}
+
+fn list_or_none(addresses: &[String]) -> String {
+    if addresses.is_empty() {
+        return "none".to_string();
+    }
+    
+    // Hosts with many IPv6 addresses would overflow the 1024 character field limit
+    let mut shown: Vec<&str> = addresses.iter().take(4).map(|ip| ip.as_str()).collect();
+    let more = format!("+{} more", addresses.len().saturating_sub(4));
+    if addresses.len() > 4 {
+        shown.push(&more);
+    }
+    shown.join(", ")
+}
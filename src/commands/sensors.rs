@@ -0,0 +1,47 @@
+use crate::{Context, Error};
+use crate::commands::health::{sensor_status, worst_sensor_status};
+use crate::utils::MAX_EMBED_FIELDS;
+use poise::serenity_prelude as serenity;
+
+/// Show hardware temperature sensors
+#[poise::command(slash_command, prefix_command)]
+pub async fn sensors(ctx: Context<'_>) -> Result<(), Error> {
+    let snapshot = ctx.data().sampler.snapshot();
+    
+    let mut sensors: Vec<_> = snapshot.sensors.iter().collect();
+    sensors.sort_by(|a, b| a.label.cmp(&b.label));
+    
+    let mut embed = serenity::CreateEmbed::new()
+        .title("🌡️ Hardware Sensors")
+        .color(worst_sensor_status(&snapshot.sensors).color())
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Sensor Monitor"));
+    
+    if sensors.is_empty() {
+        // VMs, containers and CI runners usually expose no sensors at all
+        embed = embed.description("No temperature sensors are available on this host.");
+    }
+    
+    for sensor in sensors.iter().take(MAX_EMBED_FIELDS) {
+        let status = sensor_status(sensor);
+        let critical = match sensor.critical {
+            Some(critical) => format!("{:.1}°C", critical),
+            None => "n/a".to_string(),
+        };
+        
+        embed = embed.field(
+            format!("{} {}", status.emoji(), sensor.label),
+            format!(
+                "**Current:** {:.1}°C\n**Max:** {:.1}°C\n**Critical:** {}",
+                sensor.temperature,
+                sensor.max,
+                critical
+            ),
+            true,
+        );
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    
+    Ok(())
+}
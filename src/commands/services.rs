@@ -0,0 +1,53 @@
+use crate::{Context, Error};
+use crate::commands::health::HealthStatus;
+use crate::systemd::unit_statuses;
+use crate::utils::{format_uptime, truncate, FieldBudget};
+use poise::serenity_prelude as serenity;
+
+/// Show the state of the watched systemd services
+#[poise::command(slash_command, prefix_command)]
+pub async fn services(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    
+    let units = &ctx.data().config.systemd_units;
+    let statuses = unit_statuses(units).await;
+    let worst = statuses.iter().map(|unit| unit.health()).max().unwrap_or(HealthStatus::Healthy);
+    
+    let mut embed = serenity::CreateEmbed::new()
+        .title("🛠️ Services")
+        .color(worst.color())
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Service Monitor"));
+    
+    if statuses.is_empty() {
+        embed = embed.description("No services are being watched. Add unit names to `systemd_units` in the config file.");
+    }
+    
+    // Hold back room for the title, footer and the "+N more" description
+    let mut budget = FieldBudget::new(200, 0);
+    for unit in &statuses {
+        let uptime = unit.active_secs.map(format_uptime).unwrap_or_else(|| "n/a".to_string());
+        let restarts = unit.restarts.map(|restarts| restarts.to_string()).unwrap_or_else(|| "n/a".to_string());
+        
+        let name = format!("{} {}", unit.health().emoji(), unit.name);
+        let value = format!(
+            "{}\n**State:** {} ({})\n**Uptime:** {}\n**Restarts:** {}",
+            // Unit descriptions are free text and can run long
+            truncate(&unit.description, 100),
+            unit.active_state,
+            unit.sub_state,
+            uptime,
+            restarts
+        );
+        if budget.fits(&name, &value) {
+            embed = embed.field(name, value, true);
+        }
+    }
+    if budget.skipped() > 0 {
+        embed = embed.description(format!("+{} more services that don't fit in one message.", budget.skipped()));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::{Context, Error};
+use crate::utils::{format_bytes, format_uptime, get_processes, truncate};
+use poise::serenity_prelude as serenity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ProcessSort {
+    #[name = "cpu"]
+    Cpu,
+    #[name = "memory"]
+    Memory,
+}
+
+/// List the processes using the most CPU or memory
+#[poise::command(slash_command, prefix_command)]
+pub async fn top(
+    ctx: Context<'_>,
+    #[description = "What to sort by (default: cpu)"] sort: Option<ProcessSort>,
+    #[description = "Number of processes to show (default: 10)"] limit: Option<u32>,
+) -> Result<(), Error> {
+    let sort = sort.unwrap_or(ProcessSort::Cpu);
+    let limit = limit.unwrap_or(10).clamp(1, 25) as usize; // Between 1-25 processes
+    
+    let mut processes = ctx.data().sampler.with_system(get_processes);
+    match sort {
+        ProcessSort::Cpu => processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage)),
+        ProcessSort::Memory => processes.sort_by_key(|process| std::cmp::Reverse(process.memory)),
+    }
+    let total_processes = processes.len();
+    processes.truncate(limit);
+    
+    let mut table = format!(
+        "{:>7} {:<15} {:<10} {:>6} {:>10} {:>12}\n",
+        "PID", "NAME", "USER", "CPU%", "MEM", "TIME"
+    );
+    for process in &processes {
+        table.push_str(&format!(
+            "{:>7} {:<15} {:<10} {:>6.1} {:>10} {:>12}\n",
+            process.pid,
+            truncate(&process.name, 15),
+            truncate(&process.user, 10),
+            process.cpu_usage,
+            format_bytes(process.memory),
+            format_uptime(process.run_time)
+        ));
+    }
+    
+    let title = match sort {
+        ProcessSort::Cpu => "🔥 Top Processes by CPU",
+        ProcessSort::Memory => "🧠 Top Processes by Memory",
+    };
+    let embed = serenity::CreateEmbed::new()
+        .title(title)
+        .description(format!("```\n{}```", table))
+        .field("Processes", total_processes.to_string(), true)
+        .color(0xff8c00)
+        .timestamp(chrono::Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Process Monitor"));
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    
+    Ok(())
+}
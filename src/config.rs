@@ -0,0 +1,131 @@
+use crate::alerts::AlertConfig;
+use crate::checks::HealthCheckConfig;
+use crate::monitors::MonitorConfig;
+use crate::scoring::ScoringConfig;
+use crate::utils::public_ip::{DEFAULT_IPV4_PROVIDERS, DEFAULT_IPV6_PROVIDERS};
+use crate::visibility::VisibilityConfig;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Runtime configuration, read from an optional JSON file.
+///
+/// The file path comes from `STARUST_CONFIG` (default `config.json`). A missing
+/// file is not an error, every field has a default. A few simple settings can
+/// also be overridden from the environment.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Address the embedded HTTP API listens on
+    pub api_addr: SocketAddr,
+    /// How long a looked-up public IP is reused before asking again
+    pub public_ip_ttl_secs: u64,
+    /// How long each public IP provider gets to answer before the next one is tried
+    pub public_ip_timeout_secs: u64,
+    /// Plain-text "what is my IP" services, tried in order
+    pub public_ipv4_providers: Vec<String>,
+    pub public_ipv6_providers: Vec<String>,
+    /// `/readyz` fails once the last gateway heartbeat ACK is older than this
+    pub ready_heartbeat_max_age_secs: u64,
+    /// How often a REST request is timed in the background, for `/v1/health` and `/metrics`
+    pub rest_probe_interval_secs: u64,
+    /// How often the background sampler refreshes host metrics
+    pub sampler_interval_secs: u64,
+    /// Spacing between points kept in the metric history
+    pub history_resolution_secs: u64,
+    /// How far back the metric history reaches
+    pub history_retention_hours: u64,
+    /// Where to persist the metric history, kept in memory only when unset
+    pub history_path: Option<String>,
+    /// SQLite database for command statistics, kept in memory only when `null`
+    pub stats_db_path: Option<String>,
+    /// Invocations older than this are deleted from the stats database, kept forever when `null`
+    pub stats_retention_days: Option<u64>,
+    /// Commands slower than this are logged and flagged in `/stats performance` (p95)
+    pub slow_command_ms: u64,
+    /// Threshold rules and where to announce them
+    pub alerts: AlertConfig,
+    /// Disk usage levels that count as warning/critical
+    pub disk_thresholds: DiskThresholds,
+    /// Overrides of `disk_thresholds` for individual mount points
+    pub mount_thresholds: HashMap<String, DiskThresholds>,
+    /// systemd units shown by `/services` and counted in `/health`
+    pub systemd_units: Vec<String>,
+    /// Extra probes that `/health` runs alongside its built-in checks
+    pub health_checks: HealthCheckConfig,
+    /// External endpoints probed on an interval for `/monitors`
+    pub monitors: MonitorConfig,
+    /// Weights and thresholds behind the overall health status
+    pub health_scoring: ScoringConfig,
+    /// Which host details are shown to whom
+    pub visibility: VisibilityConfig,
+}
+
+/// Usage percentages at which a disk turns warning and critical
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DiskThresholds {
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Default for DiskThresholds {
+    fn default() -> Self {
+        Self {
+            warning: 80.0,
+            critical: 90.0,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            public_ip_ttl_secs: 600,
+            public_ip_timeout_secs: 3,
+            public_ipv4_providers: DEFAULT_IPV4_PROVIDERS.iter().map(|url| url.to_string()).collect(),
+            public_ipv6_providers: DEFAULT_IPV6_PROVIDERS.iter().map(|url| url.to_string()).collect(),
+            ready_heartbeat_max_age_secs: 120,
+            rest_probe_interval_secs: 60,
+            sampler_interval_secs: 5,
+            history_resolution_secs: 60,
+            history_retention_hours: 24 * 7,
+            history_path: None,
+            stats_db_path: Some("stats.db".to_string()),
+            stats_retention_days: Some(90),
+            slow_command_ms: 2000,
+            alerts: AlertConfig::default(),
+            disk_thresholds: DiskThresholds::default(),
+            mount_thresholds: HashMap::new(),
+            systemd_units: Vec::new(),
+            health_checks: HealthCheckConfig::default(),
+            monitors: MonitorConfig::default(),
+            health_scoring: ScoringConfig::default(),
+            visibility: VisibilityConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn disk_thresholds_for(&self, mount_point: &str) -> DiskThresholds {
+        self.mount_thresholds.get(mount_point).copied().unwrap_or(self.disk_thresholds)
+    }
+
+    pub fn load() -> Result<Self, crate::Error> {
+        let path = std::env::var("STARUST_CONFIG").unwrap_or_else(|_| "config.json".to_string());
+
+        let mut config = if Path::new(&path).exists() {
+            let raw = std::fs::read_to_string(&path)?;
+            serde_json::from_str(&raw).map_err(|e| format!("Invalid config file {}: {}", path, e))?
+        } else {
+            Config::default()
+        };
+
+        if let Ok(addr) = std::env::var("API_ADDR") {
+            config.api_addr = addr.parse().map_err(|e| format!("Invalid API_ADDR `{}`: {}", addr, e))?;
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,98 @@
+use poise::serenity_prelude as serenity;
+use crate::utils::{summarize, Summary};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the shard runners are polled for their connection state
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Heartbeat latencies kept for `heartbeat_summary`, roughly an hour at Discord's ~41s interval
+const HEARTBEAT_HISTORY: usize = 90;
+
+/// Tracks whether the Discord side of the bot is actually usable.
+///
+/// A shard that silently dropped its connection keeps the process alive, so
+/// readiness is judged on what the shard runners report, not on the process.
+#[derive(Default)]
+pub struct GatewayStatus {
+    connected: AtomicBool,
+    commands_registered: AtomicBool,
+    latency: Mutex<Option<Duration>>,
+    last_heartbeat: Mutex<Option<Instant>>,
+    heartbeats: Mutex<VecDeque<Duration>>,
+}
+
+impl GatewayStatus {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn commands_registered(&self) -> bool {
+        self.commands_registered.load(Ordering::Relaxed)
+    }
+
+    pub fn set_commands_registered(&self) {
+        self.commands_registered.store(true, Ordering::Relaxed);
+    }
+
+    /// Latest heartbeat ACK latency reported by a shard
+    pub fn latency(&self) -> Option<Duration> {
+        *self.latency.lock().unwrap()
+    }
+
+    /// Time since a new heartbeat ACK was last observed
+    pub fn heartbeat_age(&self) -> Option<Duration> {
+        self.last_heartbeat.lock().unwrap().map(|at| at.elapsed())
+    }
+
+    /// Min/avg/max/p95 of the recent heartbeat ACK latencies, in milliseconds
+    pub fn heartbeat_summary(&self) -> Option<(Summary, usize)> {
+        let heartbeats: Vec<f64> = self
+            .heartbeats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|latency| latency.as_secs_f64() * 1000.0)
+            .collect();
+        summarize(&heartbeats).map(|summary| (summary, heartbeats.len()))
+    }
+
+    fn record(&self, connected: bool, latency: Option<Duration>) {
+        self.connected.store(connected, Ordering::Relaxed);
+
+        let mut last_latency = self.latency.lock().unwrap();
+        // serenity updates the latency on every ACK, so a changed value means a fresh heartbeat
+        if connected && latency != *last_latency {
+            if let Some(latency) = latency {
+                *self.last_heartbeat.lock().unwrap() = Some(Instant::now());
+
+                let mut heartbeats = self.heartbeats.lock().unwrap();
+                if heartbeats.len() == HEARTBEAT_HISTORY {
+                    heartbeats.pop_front();
+                }
+                heartbeats.push_back(latency);
+            }
+        }
+        *last_latency = latency;
+    }
+}
+
+/// Poll the shard manager in the background and keep `status` up to date
+pub fn spawn_watcher(shard_manager: Arc<serenity::ShardManager>, status: Arc<GatewayStatus>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let runners = shard_manager.runners.lock().await;
+            let connected = !runners.is_empty()
+                && runners.values().all(|runner| runner.stage == serenity::ConnectionStage::Connected);
+            let latency = runners.values().find_map(|runner| runner.latency);
+            drop(runners);
+
+            status.record(connected, latency);
+        }
+    });
+}
@@ -0,0 +1,185 @@
+use crate::gateway::GatewayStatus;
+use crate::sampler::Sampler;
+use crate::utils::write_atomic;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Persist to disk after this many new samples, so a crash loses little
+const SAVE_EVERY: usize = 10;
+
+/// One point of the rolling time series
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MetricSample {
+    pub at: DateTime<Utc>,
+    pub cpu: f32,
+    pub memory: f64,
+    pub disk: Option<f64>,
+    pub latency_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Metric {
+    #[name = "cpu"]
+    Cpu,
+    #[name = "memory"]
+    Memory,
+    #[name = "disk"]
+    Disk,
+    #[name = "latency"]
+    Latency,
+}
+
+impl Metric {
+    pub fn label(self) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU Usage",
+            Metric::Memory => "Memory Usage",
+            Metric::Disk => "Disk Usage",
+            Metric::Latency => "Gateway Latency",
+        }
+    }
+
+    pub fn format(self, value: f64) -> String {
+        match self {
+            Metric::Latency => format!("{:.0}ms", value),
+            _ => format!("{:.1}%", value),
+        }
+    }
+
+    fn value(self, sample: &MetricSample) -> Option<f64> {
+        match self {
+            Metric::Cpu => Some(sample.cpu as f64),
+            Metric::Memory => Some(sample.memory),
+            Metric::Disk => sample.disk,
+            Metric::Latency => sample.latency_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Window {
+    #[name = "1h"]
+    Hour,
+    #[name = "24h"]
+    Day,
+    #[name = "7d"]
+    Week,
+}
+
+impl Window {
+    pub fn duration(self) -> ChronoDuration {
+        match self {
+            Window::Hour => ChronoDuration::hours(1),
+            Window::Day => ChronoDuration::hours(24),
+            Window::Week => ChronoDuration::days(7),
+        }
+    }
+}
+
+/// Ring buffer of metric samples, optionally mirrored to a JSON file
+pub struct History {
+    samples: Mutex<VecDeque<MetricSample>>,
+    capacity: usize,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn new(capacity: usize, path: Option<PathBuf>) -> Self {
+        // A resolution longer than the retention works out to zero, keep at least the latest sample
+        let capacity = capacity.max(1);
+        let mut samples = VecDeque::with_capacity(capacity);
+
+        if let Some(path) = &path {
+            match std::fs::read_to_string(path) {
+                Ok(raw) => match serde_json::from_str::<Vec<MetricSample>>(&raw) {
+                    Ok(saved) => samples.extend(saved),
+                    Err(e) => tracing::warn!("Ignoring unreadable history file {}: {}", path.display(), e),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Could not read history file {}: {}", path.display(), e),
+            }
+        }
+        while samples.len() > capacity {
+            samples.pop_front();
+        }
+
+        Self {
+            samples: Mutex::new(samples),
+            capacity,
+            path,
+        }
+    }
+
+    pub fn push(&self, sample: MetricSample) {
+        let mut samples = self.samples.lock().unwrap();
+        while samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Values of `metric` recorded within the last `window`, oldest first
+    pub fn series(&self, metric: Metric, window: Window) -> Vec<f64> {
+        let since = Utc::now() - window.duration();
+        self.samples
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|sample| sample.at >= since)
+            .filter_map(|sample| metric.value(sample))
+            .collect()
+    }
+
+    fn save(&self) -> Result<(), crate::Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let samples: Vec<MetricSample> = self.samples.lock().unwrap().iter().copied().collect();
+        write_atomic(path, &serde_json::to_vec(&samples)?)?;
+
+        Ok(())
+    }
+
+    /// Record a sample every `resolution` from the sampler and gateway status
+    pub fn spawn_recorder(
+        self: Arc<Self>,
+        sampler: Arc<Sampler>,
+        gateway: Arc<GatewayStatus>,
+        resolution: Duration,
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(resolution);
+            // The first tick fires immediately, before the sampler has a real CPU reading
+            interval.tick().await;
+            let mut unsaved = 0;
+            loop {
+                interval.tick().await;
+
+                let snapshot = sampler.snapshot();
+                self.push(MetricSample {
+                    at: snapshot.taken_at,
+                    cpu: snapshot.cpu_usage() as f32,
+                    memory: snapshot.memory().usage_percent(),
+                    disk: crate::commands::health::combined_disk_usage(&snapshot.disks),
+                    latency_ms: gateway.latency().map(|latency| latency.as_secs_f64() * 1000.0),
+                });
+
+                unsaved += 1;
+                if unsaved >= SAVE_EVERY && self.path.is_some() {
+                    unsaved = 0;
+                    let history = self.clone();
+                    match tokio::task::spawn_blocking(move || history.save()).await {
+                        Ok(Err(e)) => tracing::warn!("Failed to persist metric history: {}", e),
+                        Err(e) => tracing::error!("Metric history save panicked: {}", e),
+                        Ok(Ok(())) => {}
+                    }
+                }
+            }
+        });
+    }
+}
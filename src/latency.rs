@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Milliseconds since the Unix epoch at which Discord snowflakes start counting
+const DISCORD_EPOCH_MS: u64 = 1_420_070_400_000;
+
+/// One latency reading and when it was taken
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Measurement {
+    pub ms: f64,
+    pub at: DateTime<Utc>,
+}
+
+impl Measurement {
+    fn now(latency: Duration) -> Self {
+        Self {
+            ms: latency.as_secs_f64() * 1000.0,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// The latest reading of each latency the bot can measure besides the gateway heartbeat
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LatencyReport {
+    /// A plain REST request to Discord, there and back
+    pub rest: Option<Measurement>,
+    /// From picking up a command to Discord accepting its reply
+    pub reply: Option<Measurement>,
+    /// From the snowflake timestamp of the invoking message/interaction to us handling it
+    pub dispatch_delay: Option<Measurement>,
+}
+
+/// Shared by commands, the background REST probe and the HTTP API
+#[derive(Default)]
+pub struct LatencyTracker {
+    latest: Mutex<LatencyReport>,
+}
+
+impl LatencyTracker {
+    pub fn report(&self) -> LatencyReport {
+        *self.latest.lock().unwrap()
+    }
+
+    pub fn rest(&self) -> Option<Duration> {
+        self.report().rest.map(|measurement| Duration::from_secs_f64(measurement.ms / 1000.0))
+    }
+
+    /// Time a REST request that every bot is allowed to make, recording the result
+    pub async fn measure_rest(&self, http: &serenity::Http) -> Option<Duration> {
+        let start = Instant::now();
+        match http.get_current_user().await {
+            Ok(_) => {
+                let latency = start.elapsed();
+                self.latest.lock().unwrap().rest = Some(Measurement::now(latency));
+                Some(latency)
+            }
+            Err(e) => {
+                tracing::warn!("REST latency probe failed: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn record_reply(&self, latency: Duration) {
+        self.latest.lock().unwrap().reply = Some(Measurement::now(latency));
+    }
+
+    /// Record how long ago the message or interaction `id` was created, going by its snowflake.
+    /// Relies on the host clock being in sync, a skewed clock shows up here first.
+    pub fn record_dispatch(&self, id: u64) -> Duration {
+        let delay = dispatch_delay(id);
+        self.latest.lock().unwrap().dispatch_delay = Some(Measurement::now(delay));
+        delay
+    }
+
+    /// Measure the REST round-trip every `interval`, so it is known without anyone running `/health`
+    pub fn spawn_rest_probe(self: Arc<Self>, http: Arc<serenity::Http>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                self.measure_rest(&http).await;
+            }
+        });
+    }
+}
+
+/// Time between the creation of snowflake `id` and now, zero if the clock is behind Discord's
+pub fn dispatch_delay(id: u64) -> Duration {
+    let created_ms = (id >> 22) + DISCORD_EPOCH_MS;
+    let now_ms = Utc::now().timestamp_millis().max(0) as u64;
+    Duration::from_millis(now_ms.saturating_sub(created_ms))
+}
@@ -1,17 +1,44 @@
 use poise::serenity_prelude as serenity;
-use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+#[derive(Clone)]
 pub struct Data {
     pub start_time: DateTime<Utc>,
-    pub command_count: std::sync::Arc<std::sync::Mutex<HashMap<String, u64>>>,
+    pub storage: std::sync::Arc<storage::Storage>,
+    pub public_ip: std::sync::Arc<utils::public_ip::PublicIpCache>,
+    pub gateway: std::sync::Arc<gateway::GatewayStatus>,
+    pub sampler: std::sync::Arc<sampler::Sampler>,
+    pub history: std::sync::Arc<history::History>,
+    pub alerts: std::sync::Arc<alerts::AlertEngine>,
+    pub checks: std::sync::Arc<checks::HealthChecks>,
+    pub latency: std::sync::Arc<latency::LatencyTracker>,
+    pub monitors: std::sync::Arc<monitors::Monitors>,
+    pub config: std::sync::Arc<config::Config>,
 } 
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
 
+mod alerts;
+mod api;
+mod cgroup;
+mod charts;
+mod checks;
 mod commands;
+mod config;
+mod gateway;
+mod history;
+mod latency;
+mod monitors;
+mod sampler;
+mod scoring;
+mod storage;
+mod systemd;
+#[cfg(test)]
+mod test_support;
+mod usage;
 mod utils;
+mod visibility;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -20,10 +47,54 @@ async fn main() -> Result<(), Error> {
     
     // Load environment variables
     dotenv::dotenv().ok();
+    let config = std::sync::Arc::new(config::Config::load()?);
+    
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("healthcheck") {
+        std::process::exit(api::healthcheck(config.api_addr, args.next()).await);
+    }
     
     let token = std::env::var("DISCORD_TOKEN")
         .expect("Expected DISCORD_TOKEN in environment");
     
+    // Shared between the bot and the HTTP API, so both report the same numbers
+    let data = Data {
+        start_time: Utc::now(),
+        storage: std::sync::Arc::new(storage::Storage::open(
+            config.stats_db_path.as_ref().map(std::path::Path::new),
+        )),
+        public_ip: std::sync::Arc::new(utils::public_ip::PublicIpCache::new(
+            std::time::Duration::from_secs(config.public_ip_ttl_secs),
+            std::time::Duration::from_secs(config.public_ip_timeout_secs.max(1)),
+            config.public_ipv4_providers.clone(),
+            config.public_ipv6_providers.clone(),
+        )),
+        gateway: std::sync::Arc::new(gateway::GatewayStatus::default()),
+        sampler: std::sync::Arc::new(sampler::Sampler::new()),
+        history: std::sync::Arc::new(history::History::new(
+            (config.history_retention_hours * 3600 / config.history_resolution_secs.max(1)) as usize,
+            config.history_path.as_ref().map(std::path::PathBuf::from),
+        )),
+        alerts: std::sync::Arc::new(alerts::AlertEngine::new(&config.alerts)),
+        checks: std::sync::Arc::new(checks::HealthChecks::new(&config.health_checks)),
+        latency: std::sync::Arc::new(latency::LatencyTracker::default()),
+        monitors: std::sync::Arc::new(monitors::Monitors::new(&config.monitors)),
+        config: config.clone(),
+    };
+    data.sampler.clone().spawn(std::time::Duration::from_secs(config.sampler_interval_secs.max(1)));
+    data.history.clone().spawn_recorder(
+        data.sampler.clone(),
+        data.gateway.clone(),
+        std::time::Duration::from_secs(config.history_resolution_secs.max(1)),
+    );
+    if let Some(days) = config.stats_retention_days {
+        data.storage.clone().spawn_pruner(chrono::Duration::days(days as i64));
+    }
+    data.checks.clone().spawn(
+        config.systemd_units.clone(),
+        std::time::Duration::from_secs(config.health_checks.interval_secs.max(1)),
+    );
+    
     let intents = serenity::GatewayIntents::non_privileged();
     
     let framework = poise::Framework::builder()
@@ -31,6 +102,13 @@ async fn main() -> Result<(), Error> {
             commands: vec![
                 commands::info::server_info(),
                 commands::health::health(),
+                commands::disks::disks(),
+                commands::top::top(),
+                commands::network::network(),
+                commands::sensors::sensors(),
+                commands::services::services(),
+                commands::monitors::monitors(),
+                commands::history::history(),
                 commands::fun::random_fact(),
                 commands::fun::system_joke(),
                 commands::fun::roll(),
@@ -46,8 +124,16 @@ async fn main() -> Result<(), Error> {
                 ))),
                 ..Default::default()
             },
+            pre_command: |ctx| {
+                Box::pin(async move {
+                    ctx.data().latency.record_dispatch(ctx.id());
+                    usage::pre_command(ctx).await;
+                })
+            },
+            post_command: |ctx| Box::pin(usage::post_command(ctx)),
             on_error: |error| {
                 Box::pin(async move {
+                    usage::record_failure(&error).await;
                     match error {
                         poise::FrameworkError::Command { error, ctx, .. } => {
                             tracing::error!("Error in command `{}`: {:?}", ctx.command().name, error);
@@ -66,22 +152,51 @@ async fn main() -> Result<(), Error> {
             },
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
-            Box::pin(async move {
-                poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data {
-                    start_time: Utc::now(),
-                    command_count: std::sync::Arc::new(std::sync::Mutex::new(HashMap::new())),
+        .setup({
+            let data = data.clone();
+            move |ctx, _ready, framework| {
+                Box::pin(async move {
+                    poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                    data.gateway.set_commands_registered();
+                    Ok(data)
                 })
-            })
+            }
         })
         .build();
 
-    let client = serenity::ClientBuilder::new(token, intents)
+    let mut client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
-        .await;
+        .await?;
+
+    gateway::spawn_watcher(client.shard_manager.clone(), data.gateway.clone());
+    data.latency.clone().spawn_rest_probe(
+        client.http.clone(),
+        std::time::Duration::from_secs(config.rest_probe_interval_secs.max(1)),
+    );
+    data.alerts.clone().spawn(
+        data.sampler.clone(),
+        data.gateway.clone(),
+        client.http.clone(),
+        config.alerts.channel_id.map(serenity::ChannelId::new),
+        std::time::Duration::from_secs(config.alerts.evaluation_interval_secs.max(1)),
+    );
+    data.monitors.clone().spawn(
+        client.http.clone(),
+        config.monitors.channel_id.map(serenity::ChannelId::new),
+        std::time::Duration::from_secs(config.monitors.interval_secs.max(1)),
+    );
+    
+    let api_state = api::ApiState {
+        data,
+        heartbeat_max_age: std::time::Duration::from_secs(config.ready_heartbeat_max_age_secs),
+    };
+    tokio::spawn(async move {
+        if let Err(e) = api::serve(config.api_addr, api_state).await {
+            tracing::error!("HTTP API stopped: {}", e);
+        }
+    });
 
-    client.unwrap().start().await.unwrap();
+    client.start().await?;
     
     Ok(())
 }
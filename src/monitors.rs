@@ -0,0 +1,497 @@
+use crate::checks::{run_probe, HealthCheck, ProbeConfig};
+use crate::utils::{truncate, write_atomic};
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Utc};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Availability is tracked per hour, this many hours back (30 days)
+const BUCKET_HOURS: usize = 30 * 24;
+
+/// Uptime monitoring settings from the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Channel that down/up embeds are posted to
+    pub channel_id: Option<u64>,
+    pub interval_secs: u64,
+    /// Where to keep incidents and availability across restarts, in memory only when unset
+    pub state_path: Option<String>,
+    pub targets: Vec<MonitorTarget>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            channel_id: None,
+            interval_secs: 60,
+            state_path: None,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// An endpoint to watch, using the same probe types as `health_checks`
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonitorTarget {
+    pub name: String,
+    #[serde(flatten)]
+    pub probe: ProbeConfig,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+/// A stretch of time during which a monitor was down
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub monitor: String,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the monitor is still down
+    pub ended_at: Option<DateTime<Utc>>,
+    pub reason: String,
+}
+
+/// Probe results counted within one hour
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct HourBucket {
+    hour: DateTime<Utc>,
+    checks: u32,
+    up: u32,
+}
+
+/// What is kept on disk between restarts
+#[derive(Default, Serialize, Deserialize)]
+struct SavedState {
+    incidents: Vec<Incident>,
+    buckets: HashMap<String, Vec<HourBucket>>,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    up: Option<bool>,
+    last_checked: Option<DateTime<Utc>>,
+    last_detail: String,
+    last_response_ms: Option<u128>,
+    buckets: VecDeque<HourBucket>,
+}
+
+impl MonitorState {
+    fn record(&mut self, at: DateTime<Utc>, up: bool) {
+        let hour = at.duration_trunc(ChronoDuration::hours(1)).unwrap_or(at);
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.hour == hour => {
+                bucket.checks += 1;
+                bucket.up += up as u32;
+            }
+            _ => self.buckets.push_back(HourBucket {
+                hour,
+                checks: 1,
+                up: up as u32,
+            }),
+        }
+        while self.buckets.len() > BUCKET_HOURS {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Share of successful probes since `since`, `None` without any
+    fn availability(&self, since: DateTime<Utc>) -> Option<f64> {
+        let (checks, up) = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.hour + ChronoDuration::hours(1) > since)
+            .fold((0u32, 0u32), |(checks, up), bucket| (checks + bucket.checks, up + bucket.up));
+        (checks > 0).then(|| up as f64 / checks as f64 * 100.0)
+    }
+}
+
+/// A monitor's current state, as shown by `/monitors` and `/v1/monitors`
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorStatus {
+    pub name: String,
+    /// `None` until the first probe has finished
+    pub up: Option<bool>,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub detail: String,
+    pub response_ms: Option<u128>,
+    pub availability_24h: Option<f64>,
+    pub availability_7d: Option<f64>,
+    pub availability_30d: Option<f64>,
+    /// When the ongoing outage started
+    pub down_since: Option<DateTime<Utc>>,
+}
+
+enum Transition {
+    Down { reason: String },
+    Up { incident: Incident },
+}
+
+/// Probes external endpoints on an interval and keeps their incident history
+pub struct Monitors {
+    targets: Vec<(MonitorTarget, Arc<dyn HealthCheck>)>,
+    states: Mutex<Vec<MonitorState>>,
+    incidents: Mutex<Vec<Incident>>,
+    path: Option<PathBuf>,
+}
+
+impl Monitors {
+    pub fn new(config: &MonitorConfig) -> Self {
+        let path = config.state_path.as_ref().map(PathBuf::from);
+        let mut saved = SavedState::default();
+        if let Some(path) = &path {
+            match std::fs::read_to_string(path) {
+                Ok(raw) => match serde_json::from_str(&raw) {
+                    Ok(state) => saved = state,
+                    Err(e) => tracing::warn!("Ignoring unreadable monitor state {}: {}", path.display(), e),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Could not read monitor state {}: {}", path.display(), e),
+            }
+        }
+
+        let states = config
+            .targets
+            .iter()
+            .map(|target| MonitorState {
+                // An incident left open by the last run is closed by the first successful probe
+                up: saved
+                    .incidents
+                    .iter()
+                    .any(|incident| incident.monitor == target.name && incident.ended_at.is_none())
+                    .then_some(false),
+                buckets: saved.buckets.remove(&target.name).unwrap_or_default().into(),
+                ..Default::default()
+            })
+            .collect();
+
+        Self {
+            targets: config.targets.iter().map(|target| (target.clone(), target.probe.build())).collect(),
+            states: Mutex::new(states),
+            incidents: Mutex::new(saved.incidents),
+            path,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub fn statuses(&self) -> Vec<MonitorStatus> {
+        let now = Utc::now();
+        let states = self.states.lock().unwrap();
+        let incidents = self.incidents.lock().unwrap();
+
+        self.targets
+            .iter()
+            .zip(states.iter())
+            .map(|((target, _), state)| MonitorStatus {
+                name: target.name.clone(),
+                up: state.up,
+                last_checked: state.last_checked,
+                detail: state.last_detail.clone(),
+                response_ms: state.last_response_ms,
+                availability_24h: state.availability(now - ChronoDuration::hours(24)),
+                availability_7d: state.availability(now - ChronoDuration::days(7)),
+                availability_30d: state.availability(now - ChronoDuration::days(30)),
+                down_since: incidents
+                    .iter()
+                    .rev()
+                    .find(|incident| incident.monitor == target.name && incident.ended_at.is_none())
+                    .map(|incident| incident.started_at),
+            })
+            .collect()
+    }
+
+    /// The latest `limit` incidents, newest first
+    pub fn incidents(&self, limit: usize) -> Vec<Incident> {
+        self.incidents.lock().unwrap().iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Probe every target once, concurrently, and return the up/down changes
+    async fn probe_all(&self) -> Vec<(usize, Transition)> {
+        let handles: Vec<_> = self
+            .targets
+            .iter()
+            .map(|(target, probe)| {
+                let probe = probe.clone();
+                let timeout = Duration::from_secs(target.timeout_secs.max(1));
+                tokio::spawn(async move { run_probe(probe.as_ref(), timeout).await })
+            })
+            .collect();
+
+        let mut outcomes = Vec::with_capacity(handles.len());
+        for handle in handles {
+            outcomes.push(
+                handle
+                    .await
+                    .unwrap_or_else(|e| (Err(format!("probe panicked: {}", e)), Duration::ZERO)),
+            );
+        }
+
+        let now = Utc::now();
+        let mut states = self.states.lock().unwrap();
+        let mut incidents = self.incidents.lock().unwrap();
+        let mut transitions = Vec::new();
+
+        for (index, ((outcome, elapsed), state)) in outcomes.into_iter().zip(states.iter_mut()).enumerate() {
+            let name = &self.targets[index].0.name;
+            let up = outcome.is_ok();
+            state.record(now, up);
+            state.last_checked = Some(now);
+            state.last_response_ms = Some(elapsed.as_millis());
+            state.last_detail = match outcome {
+                Ok(detail) | Err(detail) => detail,
+            };
+
+            match (state.up, up) {
+                (Some(false), true) => {
+                    let open = incidents
+                        .iter_mut()
+                        .rev()
+                        .find(|incident| &incident.monitor == name && incident.ended_at.is_none());
+                    if let Some(incident) = open {
+                        incident.ended_at = Some(now);
+                        transitions.push((index, Transition::Up { incident: incident.clone() }));
+                    }
+                }
+                // Down on the very first probe counts as an incident too
+                (Some(true) | None, false) => {
+                    incidents.push(Incident {
+                        monitor: name.clone(),
+                        started_at: now,
+                        ended_at: None,
+                        reason: state.last_detail.clone(),
+                    });
+                    transitions.push((index, Transition::Down { reason: state.last_detail.clone() }));
+                }
+                _ => {}
+            }
+            state.up = Some(up);
+        }
+
+        // Closed incidents older than the availability window are of no further use
+        let cutoff = now - ChronoDuration::hours(BUCKET_HOURS as i64);
+        incidents.retain(|incident| incident.ended_at.is_none_or(|ended_at| ended_at > cutoff));
+
+        transitions
+    }
+
+    fn save(&self) -> Result<(), crate::Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        // One lock at a time, `statuses` and `probe_all` take them in the other order
+        let buckets = self
+            .targets
+            .iter()
+            .zip(self.states.lock().unwrap().iter())
+            .map(|((target, _), state)| (target.name.clone(), state.buckets.iter().copied().collect()))
+            .collect();
+        let incidents = self.incidents.lock().unwrap().clone();
+        let saved = SavedState { incidents, buckets };
+        write_atomic(path, &serde_json::to_vec(&saved)?)?;
+
+        Ok(())
+    }
+
+    /// Probe every `interval`, posting down/up changes to `channel` when one is configured
+    pub fn spawn(self: Arc<Self>, http: Arc<serenity::Http>, channel: Option<serenity::ChannelId>, interval: Duration) {
+        if self.targets.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+
+                let transitions = self.probe_all().await;
+                for (index, transition) in transitions {
+                    let name = &self.targets[index].0.name;
+                    match &transition {
+                        Transition::Down { reason } => tracing::warn!("Monitor down: {} ({})", name, reason),
+                        Transition::Up { .. } => tracing::info!("Monitor up: {}", name),
+                    }
+
+                    let Some(channel) = channel else {
+                        continue;
+                    };
+                    let embed = monitor_embed(name, &transition);
+                    if let Err(e) = channel.send_message(&http, serenity::CreateMessage::new().embed(embed)).await {
+                        tracing::error!("Failed to post monitor change for `{}`: {}", name, e);
+                    }
+                }
+
+                if self.path.is_some() {
+                    let monitors = self.clone();
+                    match tokio::task::spawn_blocking(move || monitors.save()).await {
+                        Ok(Err(e)) => tracing::warn!("Failed to persist monitor state: {}", e),
+                        Err(e) => tracing::error!("Monitor state save panicked: {}", e),
+                        Ok(Ok(())) => {}
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn monitor_embed(name: &str, transition: &Transition) -> serenity::CreateEmbed {
+    let embed = match transition {
+        Transition::Down { reason } => serenity::CreateEmbed::new()
+            .title(format!("🔴 Monitor down: {}", name))
+            .color(0xff0000)
+            .field("Reason", truncate(reason, 1024), false),
+        Transition::Up { incident } => {
+            let downtime = incident.ended_at.unwrap_or_else(Utc::now) - incident.started_at;
+            serenity::CreateEmbed::new()
+                .title(format!("🟢 Monitor up: {}", name))
+                .color(0x00ff00)
+                .field("Down Since", format!("<t:{}:f>", incident.started_at.timestamp()), true)
+                .field("Downtime", crate::utils::format_uptime(downtime.num_seconds().max(0) as u64), true)
+                .field("Reason", truncate(&incident.reason, 1024), false)
+        }
+    };
+
+    embed
+        .timestamp(Utc::now())
+        .footer(serenity::CreateEmbedFooter::new("2tarAPI | Uptime Monitor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Stub;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Up or down as the test says
+    struct Switch(Arc<AtomicBool>);
+
+    impl HealthCheck for Switch {
+        fn check(&self) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<String, String>> + Send + '_>> {
+            let up = self.0.load(Ordering::SeqCst);
+            Box::pin(async move { if up { Ok("up".to_string()) } else { Err("connection refused".to_string()) } })
+        }
+    }
+
+    fn target(name: &str, url: &str) -> MonitorTarget {
+        MonitorTarget {
+            name: name.to_string(),
+            probe: ProbeConfig::Http {
+                url: url.to_string(),
+                expected_status: 200,
+                expected_body: None,
+            },
+            timeout_secs: 5,
+        }
+    }
+
+    fn switched(up: &Arc<AtomicBool>) -> Monitors {
+        Monitors {
+            targets: vec![(target("Website", "http://127.0.0.1:1"), Arc::new(Switch(up.clone())))],
+            states: Mutex::new(vec![MonitorState::default()]),
+            incidents: Mutex::new(Vec::new()),
+            path: None,
+        }
+    }
+
+    fn names(transitions: &[(usize, Transition)]) -> Vec<&'static str> {
+        transitions
+            .iter()
+            .map(|(_, transition)| match transition {
+                Transition::Down { .. } => "down",
+                Transition::Up { .. } => "up",
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn down_and_up_open_and_close_an_incident() {
+        let up = Arc::new(AtomicBool::new(true));
+        let monitors = switched(&up);
+        assert!(monitors.probe_all().await.is_empty());
+
+        up.store(false, Ordering::SeqCst);
+        assert_eq!(names(&monitors.probe_all().await), ["down"]);
+        assert!(monitors.probe_all().await.is_empty());
+        let status = &monitors.statuses()[0];
+        assert_eq!(status.up, Some(false));
+        assert!(status.down_since.is_some());
+
+        up.store(true, Ordering::SeqCst);
+        let transitions = monitors.probe_all().await;
+        assert_eq!(names(&transitions), ["up"]);
+        let Transition::Up { incident } = &transitions[0].1 else { unreachable!() };
+        assert_eq!(incident.reason, "connection refused");
+        assert!(incident.ended_at.is_some());
+        assert_eq!(monitors.statuses()[0].down_since, None);
+        assert_eq!(monitors.incidents(10).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn down_on_the_first_probe_is_an_incident() {
+        let monitors = switched(&Arc::new(AtomicBool::new(false)));
+        assert_eq!(names(&monitors.probe_all().await), ["down"]);
+    }
+
+    #[tokio::test]
+    async fn closed_incidents_older_than_the_window_are_pruned() {
+        let monitors = switched(&Arc::new(AtomicBool::new(true)));
+        let now = Utc::now();
+        let incident = |monitor: &str, started_days_ago: i64, ended_days_ago: Option<i64>| Incident {
+            monitor: monitor.to_string(),
+            started_at: now - ChronoDuration::days(started_days_ago),
+            ended_at: ended_days_ago.map(|days| now - ChronoDuration::days(days)),
+            reason: String::new(),
+        };
+        *monitors.incidents.lock().unwrap() = vec![
+            incident("Old", 40, Some(31)),
+            incident("Recent", 3, Some(2)),
+            // Still ongoing, however long ago it started
+            incident("Ongoing", 40, None),
+        ];
+
+        monitors.probe_all().await;
+        let kept: Vec<String> = monitors.incidents(10).into_iter().map(|incident| incident.monitor).collect();
+        assert_eq!(kept, ["Ongoing", "Recent"]);
+    }
+
+    #[test]
+    fn availability_covers_each_window() {
+        let now = Utc::now();
+        let mut state = MonitorState::default();
+        state.record(now - ChronoDuration::days(10), false);
+        state.record(now - ChronoDuration::days(10), false);
+        state.record(now - ChronoDuration::days(2), false);
+        state.record(now - ChronoDuration::hours(3), true);
+
+        assert_eq!(state.availability(now - ChronoDuration::hours(24)), Some(100.0));
+        assert_eq!(state.availability(now - ChronoDuration::days(7)), Some(50.0));
+        assert_eq!(state.availability(now - ChronoDuration::days(30)), Some(25.0));
+        assert_eq!(MonitorState::default().availability(now - ChronoDuration::days(30)), None);
+    }
+
+    #[tokio::test]
+    async fn probes_a_local_stub() {
+        let stub = Stub::serve(&[(200, "ok"), (503, "maintenance")]);
+        let monitors = Monitors::new(&MonitorConfig {
+            targets: vec![target("Stub", &stub.url)],
+            ..Default::default()
+        });
+
+        assert!(monitors.probe_all().await.is_empty());
+        assert_eq!(monitors.statuses()[0].up, Some(true));
+        assert_eq!(monitors.statuses()[0].detail, "HTTP 200");
+
+        assert_eq!(names(&monitors.probe_all().await), ["down"]);
+        assert_eq!(monitors.statuses()[0].detail, "HTTP 503, expected 200");
+        assert_eq!(stub.hits(), 2);
+    }
+}
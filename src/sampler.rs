@@ -0,0 +1,144 @@
+use crate::cgroup::ContainerInfo;
+use crate::utils::network::{get_network_interfaces, NetworkInterface};
+use crate::utils::{get_disk_usage, get_sensors, get_system_info, DiskUsage, MemoryInfo, SensorReading, SystemInfo};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use sysinfo::{System, SystemExt};
+
+/// Everything the bot reports about the host, as of the last refresh
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Utc>,
+    pub system: SystemInfo,
+    pub disks: Vec<DiskUsage>,
+    pub networks: Vec<NetworkInterface>,
+    pub sensors: Vec<SensorReading>,
+    pub container: ContainerInfo,
+}
+
+impl Snapshot {
+    /// Memory as the bot experiences it: the cgroup limit when one is set, the host otherwise
+    pub fn memory(&self) -> MemoryInfo {
+        match (self.container.memory_limit, self.container.memory_usage) {
+            (Some(limit), Some(used)) if limit < self.system.memory.total => MemoryInfo {
+                total: limit,
+                available: limit.saturating_sub(used),
+                used,
+            },
+            _ => self.system.memory.clone(),
+        }
+    }
+
+    /// Whether `memory()` reports against a cgroup limit
+    pub fn memory_limited(&self) -> bool {
+        self.container.memory_limit.is_some_and(|limit| limit < self.system.memory.total)
+    }
+
+    /// CPU usage against the cgroup quota when one is set, host-wide otherwise
+    pub fn cpu_usage(&self) -> f64 {
+        match (self.container.cpu_quota, self.container.cpu_usage_percent) {
+            (Some(_), Some(usage)) => usage,
+            _ => self.system.cpu.usage as f64,
+        }
+    }
+}
+
+/// Keeps one `System` alive and refreshes it on an interval.
+///
+/// CPU usage is computed between two refreshes, so a `System` that is built,
+/// refreshed once and dropped always reports ~0%. Sharing a long-lived one
+/// gives real numbers and lets commands answer without touching `/proc`.
+pub struct Sampler {
+    system: RwLock<System>,
+    snapshot: RwLock<Arc<Snapshot>>,
+    last_refresh: Mutex<Instant>,
+    last_cgroup_cpu_usec: Mutex<Option<u64>>,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        let started = Instant::now();
+        let mut sys = System::new_all();
+        sys.refresh_all();
+        let snapshot = take_snapshot(&sys, started.elapsed());
+
+        Self {
+            system: RwLock::new(sys),
+            last_cgroup_cpu_usec: Mutex::new(snapshot.container.cpu_usage_usec),
+            snapshot: RwLock::new(Arc::new(snapshot)),
+            last_refresh: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// The latest snapshot, cheap to call from any command
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Borrow the shared `System` for anything the snapshot doesn't carry
+    pub fn with_system<R>(&self, f: impl FnOnce(&System) -> R) -> R {
+        f(&self.system.read().unwrap())
+    }
+
+    fn refresh(&self) {
+        let elapsed = {
+            let mut last_refresh = self.last_refresh.lock().unwrap();
+            let elapsed = last_refresh.elapsed();
+            *last_refresh = Instant::now();
+            elapsed
+        };
+
+        let mut snapshot = {
+            let mut sys = self.system.write().unwrap();
+            sys.refresh_cpu();
+            sys.refresh_memory();
+            sys.refresh_disks_list();
+            // Per-process CPU usage is also measured between two refreshes
+            sys.refresh_processes();
+            // Keeps the previous counters around, so received()/transmitted() are deltas
+            sys.refresh_networks_list();
+            sys.refresh_components();
+            take_snapshot(&sys, elapsed)
+        };
+
+        // cgroup CPU time is a running total, usage is its growth over the interval
+        let mut last_cpu_usec = self.last_cgroup_cpu_usec.lock().unwrap();
+        if let (Some(previous), Some(current)) = (*last_cpu_usec, snapshot.container.cpu_usage_usec) {
+            let cores = snapshot.container.cpu_quota.unwrap_or(snapshot.system.cpu.logical_cores.max(1) as f64);
+            let used = current.saturating_sub(previous) as f64 / elapsed.as_micros().max(1) as f64;
+            snapshot.container.cpu_usage_percent = Some(used / cores * 100.0);
+        }
+        *last_cpu_usec = snapshot.container.cpu_usage_usec;
+        drop(last_cpu_usec);
+
+        *self.snapshot.write().unwrap() = Arc::new(snapshot);
+    }
+
+    /// Refresh on `interval` for as long as the process runs
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+
+                // Reading /proc and statfs blocks, keep it off the async workers
+                let sampler = self.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || sampler.refresh()).await {
+                    tracing::error!("Metrics sampler refresh panicked: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn take_snapshot(sys: &System, elapsed: Duration) -> Snapshot {
+    Snapshot {
+        taken_at: Utc::now(),
+        system: get_system_info(sys),
+        disks: get_disk_usage(sys),
+        networks: get_network_interfaces(sys, elapsed),
+        sensors: get_sensors(sys),
+        container: crate::cgroup::detect(),
+    }
+}
@@ -0,0 +1,463 @@
+use crate::alerts::ActiveAlert;
+use crate::checks::CheckResult;
+use crate::commands::health::{disk_status, sensor_status, HealthStatus};
+use crate::config::Config;
+use crate::sampler::Snapshot;
+use crate::systemd::UnitStatus;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How much each component counts towards the health score, and when it degrades.
+///
+/// Every key can be left out, down to a single `weight` or `warning` of one
+/// component; whatever is missing keeps that component's default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "ScoringOverrides")]
+pub struct ScoringConfig {
+    /// Scores below this (out of 100) are degraded
+    pub degraded_below: f64,
+    /// Scores below this are unhealthy
+    pub unhealthy_below: f64,
+    pub gateway_latency: Thresholds,
+    pub rest_latency: Thresholds,
+    pub memory: Thresholds,
+    pub cpu: Thresholds,
+    /// Disks use `disk_thresholds`/`mount_thresholds`, only the weight is set here
+    pub disk: Weight,
+    pub temperature: Weight,
+    pub services: Weight,
+    pub checks: Weight,
+    pub alerts: Weight,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            degraded_below: 85.0,
+            unhealthy_below: 50.0,
+            gateway_latency: Thresholds::new(1.0, 300.0, 1000.0),
+            rest_latency: Thresholds::new(1.0, 500.0, 1500.0),
+            memory: Thresholds::new(2.0, 80.0, 90.0),
+            cpu: Thresholds::new(1.0, 85.0, 95.0),
+            disk: Weight { weight: 2.0 },
+            temperature: Weight { weight: 1.0 },
+            services: Weight { weight: 1.0 },
+            checks: Weight { weight: 2.0 },
+            alerts: Weight { weight: 1.0 },
+        }
+    }
+}
+
+/// `ScoringConfig` as written in the config file, before the defaults are filled in
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ScoringOverrides {
+    degraded_below: Option<f64>,
+    unhealthy_below: Option<f64>,
+    gateway_latency: ThresholdsOverride,
+    rest_latency: ThresholdsOverride,
+    memory: ThresholdsOverride,
+    cpu: ThresholdsOverride,
+    disk: WeightOverride,
+    temperature: WeightOverride,
+    services: WeightOverride,
+    checks: WeightOverride,
+    alerts: WeightOverride,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+struct ThresholdsOverride {
+    weight: Option<f64>,
+    warning: Option<f64>,
+    critical: Option<f64>,
+}
+
+impl ThresholdsOverride {
+    fn over(self, defaults: Thresholds) -> Thresholds {
+        Thresholds {
+            weight: self.weight.unwrap_or(defaults.weight),
+            warning: self.warning.unwrap_or(defaults.warning),
+            critical: self.critical.unwrap_or(defaults.critical),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+struct WeightOverride {
+    weight: Option<f64>,
+}
+
+impl WeightOverride {
+    fn over(self, defaults: Weight) -> Weight {
+        Weight {
+            weight: self.weight.unwrap_or(defaults.weight),
+        }
+    }
+}
+
+impl From<ScoringOverrides> for ScoringConfig {
+    fn from(overrides: ScoringOverrides) -> Self {
+        let defaults = ScoringConfig::default();
+        Self {
+            degraded_below: overrides.degraded_below.unwrap_or(defaults.degraded_below),
+            unhealthy_below: overrides.unhealthy_below.unwrap_or(defaults.unhealthy_below),
+            gateway_latency: overrides.gateway_latency.over(defaults.gateway_latency),
+            rest_latency: overrides.rest_latency.over(defaults.rest_latency),
+            memory: overrides.memory.over(defaults.memory),
+            cpu: overrides.cpu.over(defaults.cpu),
+            disk: overrides.disk.over(defaults.disk),
+            temperature: overrides.temperature.over(defaults.temperature),
+            services: overrides.services.over(defaults.services),
+            checks: overrides.checks.over(defaults.checks),
+            alerts: overrides.alerts.over(defaults.alerts),
+        }
+    }
+}
+
+/// A component measured against warning/critical levels (milliseconds or percent)
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub weight: f64,
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Thresholds {
+    fn new(weight: f64, warning: f64, critical: f64) -> Self {
+        Self { weight, warning, critical }
+    }
+
+    fn status(&self, value: f64) -> HealthStatus {
+        if value >= self.critical {
+            HealthStatus::Unhealthy
+        } else if value >= self.warning {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        }
+    }
+
+    /// `value` plus the level it crossed, if any
+    fn describe(&self, value: f64, unit: &str) -> String {
+        match self.status(value) {
+            HealthStatus::Healthy => format!("{:.0}{}", value, unit),
+            HealthStatus::Degraded => format!("{:.0}{} (warning at {:.0}{})", value, unit, self.warning, unit),
+            HealthStatus::Unhealthy => format!("{:.0}{} (critical at {:.0}{})", value, unit, self.critical, unit),
+        }
+    }
+}
+
+/// A component whose status is decided elsewhere
+#[derive(Debug, Clone, Copy)]
+pub struct Weight {
+    pub weight: f64,
+}
+
+/// Everything the score is computed from
+pub struct HealthInputs<'a> {
+    pub snapshot: &'a Snapshot,
+    pub gateway_latency: Option<Duration>,
+    /// Not available outside a command, such as in the HTTP API
+    pub rest_latency: Option<Duration>,
+    pub services: &'a [UnitStatus],
+    pub checks: &'a [CheckResult],
+    /// The checks combined according to their own aggregation setting
+    pub checks_status: HealthStatus,
+    pub alerts: &'a [ActiveAlert],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentScore {
+    pub name: &'static str,
+    pub status: HealthStatus,
+    pub weight: f64,
+    pub detail: String,
+    /// Points of the overall score lost to this component
+    pub impact: f64,
+}
+
+impl ComponentScore {
+    /// Healthy counts fully, degraded half, unhealthy not at all
+    fn score(&self) -> f64 {
+        match self.status {
+            HealthStatus::Healthy => 100.0,
+            HealthStatus::Degraded => 50.0,
+            HealthStatus::Unhealthy => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    /// Weighted average of the component scores, out of 100
+    pub score: f64,
+    pub components: Vec<ComponentScore>,
+}
+
+impl HealthReport {
+    /// Components that pulled the score down, the most costly first
+    pub fn downgrades(&self) -> Vec<&ComponentScore> {
+        let mut downgrades: Vec<_> = self
+            .components
+            .iter()
+            .filter(|component| component.status != HealthStatus::Healthy)
+            .collect();
+        downgrades.sort_by(|a, b| b.impact.total_cmp(&a.impact));
+        downgrades
+    }
+}
+
+/// Score every component that can be measured right now and combine them.
+///
+/// Components without data (no sensors, no services configured, no REST
+/// timing) are left out rather than counted as healthy. The result is never
+/// better than the worst component, however small its weight; the score can
+/// only make it worse, when several components are down at once.
+pub fn evaluate(config: &Config, inputs: &HealthInputs) -> HealthReport {
+    let scoring = &config.health_scoring;
+    let snapshot = inputs.snapshot;
+    let mut components = Vec::new();
+
+    if let Some(latency) = inputs.gateway_latency {
+        let ms = latency.as_secs_f64() * 1000.0;
+        components.push(ComponentScore {
+            name: "Gateway Latency",
+            status: scoring.gateway_latency.status(ms),
+            weight: scoring.gateway_latency.weight,
+            detail: scoring.gateway_latency.describe(ms, "ms"),
+            impact: 0.0,
+        });
+    }
+
+    if let Some(latency) = inputs.rest_latency {
+        let ms = latency.as_secs_f64() * 1000.0;
+        components.push(ComponentScore {
+            name: "REST Round-Trip",
+            status: scoring.rest_latency.status(ms),
+            weight: scoring.rest_latency.weight,
+            detail: scoring.rest_latency.describe(ms, "ms"),
+            impact: 0.0,
+        });
+    }
+
+    let memory = snapshot.memory().usage_percent();
+    components.push(ComponentScore {
+        name: "Memory",
+        status: scoring.memory.status(memory),
+        weight: scoring.memory.weight,
+        detail: scoring.memory.describe(memory, "%"),
+        impact: 0.0,
+    });
+
+    let cpu = snapshot.cpu_usage();
+    components.push(ComponentScore {
+        name: "CPU",
+        status: scoring.cpu.status(cpu),
+        weight: scoring.cpu.weight,
+        detail: scoring.cpu.describe(cpu, "%"),
+        impact: 0.0,
+    });
+
+    let worst_disk = snapshot
+        .disks
+        .iter()
+        .map(|disk| (disk_status(disk, config), disk))
+        .max_by_key(|(status, _)| *status);
+    if let Some((status, disk)) = worst_disk {
+        components.push(ComponentScore {
+            name: "Disk",
+            status,
+            weight: scoring.disk.weight,
+            detail: format!("{} at {:.0}%", disk.mount_point, disk.usage_percent()),
+            impact: 0.0,
+        });
+    }
+
+    let hottest = snapshot
+        .sensors
+        .iter()
+        .map(|sensor| (sensor_status(sensor), sensor))
+        .max_by(|(a, x), (b, y)| a.cmp(b).then(x.temperature.total_cmp(&y.temperature)));
+    if let Some((status, sensor)) = hottest {
+        components.push(ComponentScore {
+            name: "Temperature",
+            status,
+            weight: scoring.temperature.weight,
+            detail: format!("{} at {:.0}°C", sensor.label, sensor.temperature),
+            impact: 0.0,
+        });
+    }
+
+    if !inputs.services.is_empty() {
+        let down: Vec<&str> = inputs
+            .services
+            .iter()
+            .filter(|unit| unit.health() != HealthStatus::Healthy)
+            .map(|unit| unit.name.as_str())
+            .collect();
+        components.push(ComponentScore {
+            name: "Services",
+            status: crate::commands::health::worst_unit_status(inputs.services),
+            weight: scoring.services.weight,
+            detail: if down.is_empty() {
+                format!("{} active", inputs.services.len())
+            } else {
+                format!("not active: {}", down.join(", "))
+            },
+            impact: 0.0,
+        });
+    }
+
+    if !inputs.checks.is_empty() {
+        let failing: Vec<&str> = inputs
+            .checks
+            .iter()
+            .filter(|check| check.status != HealthStatus::Healthy)
+            .map(|check| check.name.as_str())
+            .collect();
+        components.push(ComponentScore {
+            name: "Checks",
+            status: inputs.checks_status,
+            weight: scoring.checks.weight,
+            detail: if failing.is_empty() {
+                format!("{} passing", inputs.checks.len())
+            } else {
+                format!("failing: {}", failing.join(", "))
+            },
+            impact: 0.0,
+        });
+    }
+
+    components.push(ComponentScore {
+        name: "Alerts",
+        status: if inputs.alerts.is_empty() { HealthStatus::Healthy } else { HealthStatus::Degraded },
+        weight: scoring.alerts.weight,
+        detail: if inputs.alerts.is_empty() {
+            "none firing".to_string()
+        } else {
+            format!(
+                "firing: {}",
+                inputs.alerts.iter().map(|alert| alert.name.as_str()).collect::<Vec<_>>().join(", ")
+            )
+        },
+        impact: 0.0,
+    });
+
+    let total_weight: f64 = components.iter().map(|component| component.weight.max(0.0)).sum();
+    let score = if total_weight > 0.0 {
+        for component in &mut components {
+            component.impact = component.weight.max(0.0) * (100.0 - component.score()) / total_weight;
+        }
+        100.0 - components.iter().map(|component| component.impact).sum::<f64>()
+    } else {
+        100.0
+    };
+
+    let scored = if score < scoring.unhealthy_below {
+        HealthStatus::Unhealthy
+    } else if score < scoring.degraded_below {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    };
+    // A component weighted out of the score can't set the status floor either
+    let worst = components
+        .iter()
+        .filter(|component| component.weight > 0.0)
+        .map(|component| component.status)
+        .max()
+        .unwrap_or(HealthStatus::Healthy);
+    let status = scored.max(worst);
+
+    HealthReport {
+        status,
+        score,
+        components,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_snapshot;
+
+    fn report(config: &Config, snapshot: &Snapshot) -> HealthReport {
+        evaluate(
+            config,
+            &HealthInputs {
+                snapshot,
+                gateway_latency: Some(Duration::from_millis(50)),
+                rest_latency: None,
+                services: &[],
+                checks: &[],
+                checks_status: HealthStatus::Healthy,
+                alerts: &[],
+            },
+        )
+    }
+
+    #[test]
+    fn partial_overrides_keep_component_defaults() {
+        let config: ScoringConfig = serde_json::from_str(
+            r#"{"memory": {"weight": 3}, "cpu": {"critical": 99}, "disk": {}, "degraded_below": 90}"#,
+        )
+        .unwrap();
+        assert_eq!(config.memory.weight, 3.0);
+        assert_eq!(config.memory.warning, 80.0);
+        assert_eq!(config.memory.critical, 90.0);
+        assert_eq!(config.cpu.warning, 85.0);
+        assert_eq!(config.cpu.critical, 99.0);
+        assert_eq!(config.disk.weight, 2.0);
+        assert_eq!(config.degraded_below, 90.0);
+        assert_eq!(config.unhealthy_below, 50.0);
+    }
+
+    #[test]
+    fn all_healthy_scores_full_marks() {
+        let report = report(&Config::default(), &test_snapshot(40, 40));
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert_eq!(report.score, 100.0);
+        assert!(report.downgrades().is_empty());
+    }
+
+    #[test]
+    fn one_degraded_component_degrades_the_status() {
+        let config = Config::default();
+        for report in [report(&config, &test_snapshot(85, 40)), report(&config, &test_snapshot(40, 85))] {
+            // A single heavy component still only costs a few points
+            assert!(report.score > config.health_scoring.degraded_below);
+            assert_eq!(report.status, HealthStatus::Degraded);
+            assert_eq!(report.downgrades().len(), 1);
+        }
+    }
+
+    #[test]
+    fn critical_memory_is_unhealthy() {
+        let report = report(&Config::default(), &test_snapshot(95, 40));
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert_eq!(report.downgrades()[0].name, "Memory");
+    }
+
+    #[test]
+    fn zero_weight_components_do_not_set_the_status() {
+        let mut config = Config::default();
+        config.health_scoring.memory.weight = 0.0;
+        let report = report(&config, &test_snapshot(95, 40));
+        assert_eq!(report.score, 100.0);
+        assert_eq!(report.status, HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn low_score_is_unhealthy_without_any_unhealthy_component() {
+        let mut config = Config::default();
+        config.health_scoring.unhealthy_below = 80.0;
+        config.health_scoring.degraded_below = 95.0;
+        // Gateway, memory, CPU, disk and alerts weigh 7, memory and disk degraded together cost (2 + 2) * 50 / 7
+        let report = report(&config, &test_snapshot(85, 85));
+        assert!((report.score - (100.0 - 200.0 / 7.0)).abs() < 1e-9);
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+    }
+}
@@ -0,0 +1,587 @@
+use crate::usage::{CommandPerformance, CommandTotals, Invocation, InvocationKind, RecordedError};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Schema changes, applied in order. `PRAGMA user_version` holds how many have run,
+/// so only append to this list, never edit an entry that has shipped.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE invocations (
+        id INTEGER PRIMARY KEY,
+        at_ms INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        guild_id INTEGER,
+        user_id INTEGER NOT NULL,
+        success INTEGER NOT NULL,
+        duration_ms REAL NOT NULL
+    );
+    CREATE INDEX invocations_at ON invocations (at_ms);
+    CREATE INDEX invocations_command ON invocations (command);",
+    "ALTER TABLE invocations ADD COLUMN channel_id INTEGER;
+    CREATE INDEX invocations_guild ON invocations (guild_id, at_ms);
+    CREATE INDEX invocations_user ON invocations (user_id, at_ms);
+    CREATE INDEX invocations_channel ON invocations (channel_id, at_ms);",
+    "ALTER TABLE invocations ADD COLUMN error TEXT;",
+    // Lifetime totals for `/metrics`, kept up to date on insert and untouched by retention
+    "CREATE TABLE command_counters (
+        command TEXT PRIMARY KEY,
+        uses INTEGER NOT NULL,
+        failures INTEGER NOT NULL,
+        slash INTEGER NOT NULL,
+        prefix INTEGER NOT NULL,
+        duration_ms REAL NOT NULL
+    );
+    INSERT INTO command_counters
+    SELECT command, COUNT(*), SUM(success = 0), SUM(kind = 'slash'), SUM(kind = 'prefix'), SUM(duration_ms)
+    FROM invocations
+    GROUP BY command;",
+];
+
+/// Restricts a query to the filter's window and scope, binding parameters `?1` to `?4`
+const FILTER: &str = "at_ms >= ?1
+    AND (?2 IS NULL OR guild_id = ?2)
+    AND (?3 IS NULL OR user_id = ?3)
+    AND (?4 IS NULL OR channel_id = ?4)";
+
+/// How far back `/stats` looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum StatsWindow {
+    #[name = "1h"]
+    Hour,
+    #[name = "24h"]
+    Day,
+    #[name = "7d"]
+    Week,
+    #[name = "30d"]
+    Month,
+    #[name = "all"]
+    All,
+}
+
+impl StatsWindow {
+    /// `None` for all time
+    pub fn duration(self) -> Option<ChronoDuration> {
+        match self {
+            StatsWindow::Hour => Some(ChronoDuration::hours(1)),
+            StatsWindow::Day => Some(ChronoDuration::hours(24)),
+            StatsWindow::Week => Some(ChronoDuration::days(7)),
+            StatsWindow::Month => Some(ChronoDuration::days(30)),
+            StatsWindow::All => None,
+        }
+    }
+}
+
+/// Whose invocations `/stats` counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum StatsScope {
+    #[name = "global"]
+    Global,
+    #[name = "guild"]
+    Guild,
+    #[name = "user"]
+    User,
+    #[name = "channel"]
+    Channel,
+}
+
+/// Which invocations a stats query covers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub guild_id: Option<u64>,
+    pub user_id: Option<u64>,
+    pub channel_id: Option<u64>,
+}
+
+impl StatsFilter {
+    fn params(&self) -> (i64, Option<i64>, Option<i64>, Option<i64>) {
+        (
+            self.since.map_or(i64::MIN, |since| since.timestamp_millis()),
+            self.guild_id.map(|id| id as i64),
+            self.user_id.map(|id| id as i64),
+            self.channel_id.map(|id| id as i64),
+        )
+    }
+}
+
+/// Command statistics in an embedded SQLite database, kept across restarts
+pub struct Storage {
+    conn: Mutex<Connection>,
+}
+
+impl Storage {
+    /// Open (or create) the database at `path` and bring its schema up to date.
+    /// Without a path, or when the file can't be used, the statistics live in memory
+    /// for this run only.
+    pub fn open(path: Option<&Path>) -> Self {
+        let conn = match path.map(|path| Self::open_file(path).map_err(|e| (path, e))) {
+            Some(Ok(conn)) => conn,
+            Some(Err((path, e))) => {
+                tracing::warn!("Could not open stats database {}, keeping stats in memory: {}", path.display(), e);
+                Self::open_memory()
+            }
+            None => Self::open_memory(),
+        };
+
+        Self { conn: Mutex::new(conn) }
+    }
+
+    fn open_file(path: &Path) -> Result<Connection, crate::Error> {
+        let mut conn = Connection::open(path)?;
+        // Readers don't block the hooks writing new invocations
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        migrate(&mut conn)?;
+        Ok(conn)
+    }
+
+    fn open_memory() -> Connection {
+        let mut conn = Connection::open_in_memory().expect("in-memory SQLite is always available");
+        migrate(&mut conn).expect("migrations apply to an empty database");
+        conn
+    }
+
+    pub fn record(&self, invocation: &Invocation) {
+        let mut conn = self.conn.lock().unwrap();
+        if let Err(e) = insert(&mut conn, invocation) {
+            tracing::warn!("Could not record `{}` invocation: {}", invocation.command, e);
+        }
+    }
+
+    /// Delete invocations from before `cutoff`, returning how many went.
+    /// The lifetime counters keep counting them.
+    pub fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM invocations WHERE at_ms < ?1", params![cutoff.timestamp_millis()])?)
+    }
+
+    /// Run `query` on the blocking thread pool, SQLite reads block just like writes
+    pub async fn read<T, F>(self: &Arc<Self>, query: F) -> Result<T, crate::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Storage) -> Result<T, crate::Error> + Send + 'static,
+    {
+        let storage = self.clone();
+        tokio::task::spawn_blocking(move || query(&storage)).await?
+    }
+
+    /// Prune invocations older than `retention` every hour for as long as the process runs
+    pub fn spawn_pruner(self: Arc<Self>, retention: ChronoDuration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+
+                let storage = self.clone();
+                let cutoff = Utc::now() - retention;
+                match tokio::task::spawn_blocking(move || storage.prune(cutoff)).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(pruned)) => tracing::info!("Pruned {} invocations older than {}", pruned, cutoff),
+                    Ok(Err(e)) => tracing::warn!("Could not prune the stats database: {}", e),
+                    Err(e) => tracing::error!("Pruning the stats database panicked: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Totals per command since the database was created, including pruned invocations
+    pub fn lifetime_totals(&self) -> Result<Vec<(String, CommandTotals)>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT command, uses, failures, slash, prefix, duration_ms
+             FROM command_counters
+             ORDER BY uses DESC, command",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                CommandTotals {
+                    uses: row.get::<_, i64>(1)? as u64,
+                    failures: row.get::<_, i64>(2)? as u64,
+                    slash: row.get::<_, i64>(3)? as u64,
+                    prefix: row.get::<_, i64>(4)? as u64,
+                    total_duration: Duration::from_secs_f64(row.get::<_, f64>(5)?.max(0.0) / 1000.0),
+                },
+            ))
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Totals per command, the most used first
+    pub fn command_totals(&self, filter: &StatsFilter) -> Result<Vec<(String, CommandTotals)>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT command,
+                    COUNT(*),
+                    SUM(success = 0),
+                    SUM(kind = ?5),
+                    SUM(kind = ?6),
+                    SUM(duration_ms)
+             FROM invocations
+             WHERE {}
+             GROUP BY command
+             ORDER BY COUNT(*) DESC, command",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(
+            params![since, guild, user, channel, InvocationKind::Slash.as_str(), InvocationKind::Prefix.as_str()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    CommandTotals {
+                        uses: row.get::<_, i64>(1)? as u64,
+                        failures: row.get::<_, i64>(2)? as u64,
+                        slash: row.get::<_, i64>(3)? as u64,
+                        prefix: row.get::<_, i64>(4)? as u64,
+                        total_duration: Duration::from_secs_f64(row.get::<_, f64>(5)?.max(0.0) / 1000.0),
+                    },
+                ))
+            },
+        )?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// When the oldest matching invocation happened, `None` while there is none
+    pub fn first_invocation(&self, filter: &StatsFilter) -> Result<Option<DateTime<Utc>>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let (since, guild, user, channel) = filter.params();
+        let at_ms: Option<i64> = conn
+            .query_row(
+                &format!("SELECT MIN(at_ms) FROM invocations WHERE {}", FILTER),
+                params![since, guild, user, channel],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+
+        Ok(at_ms.and_then(|ms| Utc.timestamp_millis_opt(ms).single()))
+    }
+
+    pub fn unique_users(&self, filter: &StatsFilter) -> Result<u64, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let (since, guild, user, channel) = filter.params();
+        let users: i64 = conn.query_row(
+            &format!("SELECT COUNT(DISTINCT user_id) FROM invocations WHERE {}", FILTER),
+            params![since, guild, user, channel],
+            |row| row.get(0),
+        )?;
+
+        Ok(users as u64)
+    }
+
+    /// Invocations per hour of the day (UTC), index 0 being midnight to 1am
+    pub fn hour_of_day(&self, filter: &StatsFilter) -> Result<[u64; 24], crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT (at_ms / 3600000) % 24, COUNT(*) FROM invocations WHERE {} GROUP BY 1",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(params![since, guild, user, channel], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut hours = [0; 24];
+        for row in rows {
+            let (hour, count) = row?;
+            // Invocations before 1970 would give a negative hour, not that any exist
+            hours[hour.rem_euclid(24) as usize] += count as u64;
+        }
+        Ok(hours)
+    }
+
+    /// The `limit` most active users of every command, as `(user ID, uses)`
+    pub fn top_users(&self, filter: &StatsFilter, limit: usize) -> Result<HashMap<String, Vec<(u64, u64)>>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT command, user_id, uses FROM (
+                SELECT command,
+                       user_id,
+                       COUNT(*) AS uses,
+                       ROW_NUMBER() OVER (PARTITION BY command ORDER BY COUNT(*) DESC, user_id) AS rank
+                FROM invocations
+                WHERE {}
+                GROUP BY command, user_id
+             )
+             WHERE rank <= ?5
+             ORDER BY command, rank",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(params![since, guild, user, channel, limit as i64], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)? as u64,
+                row.get::<_, i64>(2)? as u64,
+            ))
+        })?;
+
+        let mut top = HashMap::<String, Vec<(u64, u64)>>::new();
+        for row in rows {
+            let (command, user_id, uses) = row?;
+            top.entry(command).or_default().push((user_id, uses));
+        }
+        Ok(top)
+    }
+
+    /// Uses, error rate and run time percentiles per command, the most used first
+    pub fn performance(&self, filter: &StatsFilter) -> Result<Vec<CommandPerformance>, crate::Error> {
+        let totals = self.command_totals(filter)?;
+
+        let conn = self.conn.lock().unwrap();
+        // Nearest-rank percentiles, worked out by SQLite so the durations never leave the database.
+        // Invocations rejected before they ran are stored with a zero duration and left out.
+        let mut statement = conn.prepare(&format!(
+            "SELECT command,
+                MAX(CASE WHEN position = MAX(1, (50 * n + 99) / 100) THEN duration_ms END),
+                MAX(CASE WHEN position = MAX(1, (95 * n + 99) / 100) THEN duration_ms END),
+                MAX(CASE WHEN position = MAX(1, (99 * n + 99) / 100) THEN duration_ms END)
+             FROM (
+                SELECT command, duration_ms,
+                    ROW_NUMBER() OVER (PARTITION BY command ORDER BY duration_ms) AS position,
+                    COUNT(*) OVER (PARTITION BY command) AS n
+                FROM invocations
+                WHERE {} AND duration_ms > 0
+             )
+             GROUP BY command",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(params![since, guild, user, channel], |row| {
+            Ok((row.get::<_, String>(0)?, (row.get::<_, f64>(1)?, row.get::<_, f64>(2)?, row.get::<_, f64>(3)?)))
+        })?;
+        let mut percentiles = rows.collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(totals
+            .into_iter()
+            .map(|(command, totals)| {
+                let pcts = percentiles.remove(&command);
+                CommandPerformance {
+                    p50: pcts.map(|(p50, _, _)| p50),
+                    p95: pcts.map(|(_, p95, _)| p95),
+                    p99: pcts.map(|(_, _, p99)| p99),
+                    command,
+                    uses: totals.uses,
+                    failures: totals.failures,
+                }
+            })
+            .collect())
+    }
+
+    /// The latest `limit` matching invocations, oldest first
+    pub fn invocations(&self, filter: &StatsFilter, limit: usize) -> Result<Vec<Invocation>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT at_ms, command, kind, guild_id, channel_id, user_id, success, error, duration_ms
+             FROM invocations
+             WHERE {}
+             ORDER BY at_ms DESC
+             LIMIT ?5",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(params![since, guild, user, channel, limit as i64], |row| {
+            Ok(Invocation {
+                at: Utc.timestamp_millis_opt(row.get(0)?).single().unwrap_or_default(),
+                command: row.get(1)?,
+                // Only the two kinds are ever written
+                kind: match row.get::<_, String>(2)?.as_str() {
+                    "prefix" => InvocationKind::Prefix,
+                    _ => InvocationKind::Slash,
+                },
+                guild_id: row.get::<_, Option<i64>>(3)?.map(|id| id as u64),
+                channel_id: row.get::<_, Option<i64>>(4)?.map(|id| id as u64),
+                user_id: row.get::<_, i64>(5)? as u64,
+                success: row.get(6)?,
+                error: row.get(7)?,
+                duration: Duration::from_secs_f64(row.get::<_, f64>(8)?.max(0.0) / 1000.0),
+            })
+        })?;
+
+        let mut invocations = rows.collect::<Result<Vec<_>, _>>()?;
+        invocations.reverse();
+        Ok(invocations)
+    }
+
+    /// The latest `limit` failed invocations, newest first
+    pub fn recent_errors(&self, filter: &StatsFilter, limit: usize) -> Result<Vec<RecordedError>, crate::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&format!(
+            "SELECT command, error, at_ms FROM invocations
+             WHERE {} AND success = 0
+             ORDER BY at_ms DESC
+             LIMIT ?5",
+            FILTER
+        ))?;
+        let (since, guild, user, channel) = filter.params();
+        let rows = statement.query_map(params![since, guild, user, channel, limit as i64], |row| {
+            Ok(RecordedError {
+                command: row.get(0)?,
+                // Failures recorded before errors were stored have none
+                error: row.get::<_, Option<String>>(1)?.unwrap_or_else(|| "unknown error".to_string()),
+                at: Utc.timestamp_millis_opt(row.get(2)?).single().unwrap_or_default(),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+/// Store one invocation and count it towards the lifetime counters, both or neither
+fn insert(conn: &mut Connection, invocation: &Invocation) -> Result<(), rusqlite::Error> {
+    let tx = conn.transaction()?;
+    let duration_ms = invocation.duration.as_secs_f64() * 1000.0;
+    tx.execute(
+        "INSERT INTO invocations (at_ms, command, kind, guild_id, channel_id, user_id, success, error, duration_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            invocation.at.timestamp_millis(),
+            invocation.command,
+            invocation.kind.as_str(),
+            invocation.guild_id.map(|id| id as i64),
+            invocation.channel_id.map(|id| id as i64),
+            invocation.user_id as i64,
+            invocation.success,
+            invocation.error,
+            duration_ms,
+        ],
+    )?;
+    tx.execute(
+        "INSERT INTO command_counters (command, uses, failures, slash, prefix, duration_ms)
+         VALUES (?1, 1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (command) DO UPDATE SET
+            uses = uses + 1,
+            failures = failures + excluded.failures,
+            slash = slash + excluded.slash,
+            prefix = prefix + excluded.prefix,
+            duration_ms = duration_ms + excluded.duration_ms",
+        params![
+            invocation.command,
+            !invocation.success,
+            invocation.kind == InvocationKind::Slash,
+            invocation.kind == InvocationKind::Prefix,
+            duration_ms,
+        ],
+    )?;
+    tx.commit()
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), rusqlite::Error> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+        tracing::info!("Applied stats database migration {}", version + 1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(command: &str, duration_ms: u64, success: bool) -> Invocation {
+        Invocation {
+            command: command.to_string(),
+            kind: InvocationKind::Slash,
+            success,
+            error: (!success).then(|| "failed".to_string()),
+            duration: Duration::from_millis(duration_ms),
+            guild_id: Some(1),
+            channel_id: Some(2),
+            user_id: 3,
+            at: Utc::now(),
+        }
+    }
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn migrates_an_empty_database_to_the_latest_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(user_version(&conn), 0);
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+
+        // Running it again is a no-op
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn migrates_from_the_previous_version_keeping_invocations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let previous = MIGRATIONS.len() - 1;
+        for migration in &MIGRATIONS[..previous] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.pragma_update(None, "user_version", previous).unwrap();
+        conn.execute_batch(
+            "INSERT INTO invocations (at_ms, command, kind, guild_id, channel_id, user_id, success, error, duration_ms) VALUES
+                (1, 'ping', 'slash', 1, 2, 3, 1, NULL, 10.0),
+                (2, 'ping', 'prefix', 1, 2, 3, 0, 'failed', 30.0),
+                (3, 'roll', 'slash', NULL, 2, 3, 1, NULL, 5.0);",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+
+        let storage = Storage { conn: Mutex::new(conn) };
+        assert_eq!(storage.command_totals(&StatsFilter::default()).unwrap().len(), 2);
+        // The counters start out with everything already recorded
+        let (command, totals) = &storage.lifetime_totals().unwrap()[0];
+        assert_eq!(command, "ping");
+        assert_eq!((totals.uses, totals.failures, totals.slash, totals.prefix), (2, 1, 1, 1));
+        assert_eq!(totals.total_duration, Duration::from_millis(40));
+    }
+
+    #[test]
+    fn performance_uses_nearest_rank_percentiles() {
+        let storage = Storage::open(None);
+        for ms in (1..=100).rev() {
+            storage.record(&invocation("ping", ms, true));
+        }
+        // Stopped before it ran, counted as a failure but not in the percentiles
+        storage.record(&invocation("ping", 0, false));
+        storage.record(&invocation("roll", 7, true));
+        storage.record(&invocation("admin", 0, false));
+
+        let performance = storage.performance(&StatsFilter::default()).unwrap();
+        let find = |command: &str| performance.iter().find(|p| p.command == command).unwrap();
+
+        let ping = find("ping");
+        assert_eq!((ping.uses, ping.failures), (101, 1));
+        assert_eq!((ping.p50, ping.p95, ping.p99), (Some(50.0), Some(95.0), Some(99.0)));
+        let roll = find("roll");
+        assert_eq!((roll.p50, roll.p95, roll.p99), (Some(7.0), Some(7.0), Some(7.0)));
+        assert_eq!(find("admin").p50, None);
+    }
+
+    #[test]
+    fn lifetime_totals_survive_pruning() {
+        let storage = Storage::open(None);
+        let mut old = invocation("ping", 10, true);
+        old.at = Utc::now() - ChronoDuration::days(100);
+        storage.record(&old);
+        storage.record(&invocation("ping", 30, false));
+
+        assert_eq!(storage.prune(Utc::now() - ChronoDuration::days(90)).unwrap(), 1);
+        assert_eq!(storage.command_totals(&StatsFilter::default()).unwrap()[0].1.uses, 1);
+
+        let (command, totals) = &storage.lifetime_totals().unwrap()[0];
+        assert_eq!(command, "ping");
+        assert_eq!((totals.uses, totals.failures, totals.slash, totals.prefix), (2, 1, 2, 0));
+        assert_eq!(totals.total_duration, Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::commands::health::HealthStatus;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const PROPERTIES: &str = "Id,Description,LoadState,ActiveState,SubState,ActiveEnterTimestampMonotonic,NRestarts";
+const SYSTEMCTL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitStatus {
+    pub name: String,
+    pub description: String,
+    pub load_state: String,
+    pub active_state: String,
+    pub sub_state: String,
+    /// How long the unit has been in its active state
+    pub active_secs: Option<u64>,
+    pub restarts: Option<u64>,
+}
+
+impl UnitStatus {
+    /// A watched unit that isn't running is worth a look, one that failed is a problem
+    pub fn health(&self) -> HealthStatus {
+        match self.active_state.as_str() {
+            "active" => HealthStatus::Healthy,
+            "failed" => HealthStatus::Unhealthy,
+            _ => HealthStatus::Degraded,
+        }
+    }
+
+    fn unknown(name: &str, reason: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: reason.to_string(),
+            load_state: "unknown".to_string(),
+            active_state: "unknown".to_string(),
+            sub_state: "unknown".to_string(),
+            active_secs: None,
+            restarts: None,
+        }
+    }
+}
+
+/// Query systemd for `units` in one `systemctl show` call.
+///
+/// Hosts without systemd (containers, other init systems) get every unit back
+/// as `unknown` instead of an error, so callers can always render something.
+pub async fn unit_statuses(units: &[String]) -> Vec<UnitStatus> {
+    if units.is_empty() {
+        return Vec::new();
+    }
+
+    let output = tokio::time::timeout(
+        SYSTEMCTL_TIMEOUT,
+        tokio::process::Command::new("systemctl")
+            .arg("show")
+            .arg(format!("--property={}", PROPERTIES))
+            .arg("--")
+            .args(units)
+            // Dropped on timeout, which must not leave the child running
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    let stdout = match output {
+        Ok(Ok(output)) if output.status.success() => String::from_utf8_lossy(&output.stdout).to_string(),
+        Ok(Ok(output)) => {
+            let reason = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return units.iter().map(|unit| UnitStatus::unknown(unit, &reason)).collect();
+        }
+        Ok(Err(e)) => {
+            tracing::debug!("systemctl unavailable: {}", e);
+            return units.iter().map(|unit| UnitStatus::unknown(unit, "systemd is not available on this host")).collect();
+        }
+        Err(_) => {
+            return units.iter().map(|unit| UnitStatus::unknown(unit, "systemctl timed out")).collect();
+        }
+    };
+
+    parse_show(&stdout, units, monotonic_usec())
+}
+
+/// Statuses of `units` from `systemctl show` output, `now` being CLOCK_MONOTONIC in microseconds
+fn parse_show(stdout: &str, units: &[String], now: Option<u64>) -> Vec<UnitStatus> {
+    // systemctl prints one block of properties per unit, separated by blank lines, in argument order
+    let blocks: Vec<HashMap<&str, &str>> = stdout
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| block.lines().filter_map(|line| line.split_once('=')).collect())
+        .collect();
+
+    units
+        .iter()
+        .enumerate()
+        .map(|(index, unit)| {
+            let Some(properties) = blocks.get(index) else {
+                return UnitStatus::unknown(unit, "missing from systemctl output");
+            };
+            let property = |key: &str| properties.get(key).copied().unwrap_or_default().to_string();
+
+            let active_state = property("ActiveState");
+            // Only meaningful while the unit is active, systemd reports 0 when it never was
+            let active_secs = match (active_state.as_str(), property("ActiveEnterTimestampMonotonic").parse::<u64>()) {
+                ("active", Ok(entered)) if entered > 0 => now.map(|now| now.saturating_sub(entered) / 1_000_000),
+                _ => None,
+            };
+
+            UnitStatus {
+                name: unit.clone(),
+                description: property("Description"),
+                load_state: property("LoadState"),
+                active_state,
+                sub_state: property("SubState"),
+                active_secs,
+                restarts: property("NRestarts").parse().ok(),
+            }
+        })
+        .collect()
+}
+
+/// systemd timestamps its state changes with CLOCK_MONOTONIC
+fn monotonic_usec() -> Option<u64> {
+    // SAFETY: `ts` is a plain C struct that clock_gettime fills in on success.
+    let ts = unsafe {
+        let mut ts: libc::timespec = std::mem::zeroed();
+        if libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) != 0 {
+            return None;
+        }
+        ts
+    };
+    Some(ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOW: &str = "Id=nginx.service
+Description=A high performance web server
+LoadState=loaded
+ActiveState=active
+SubState=running
+ActiveEnterTimestampMonotonic=5000000
+NRestarts=2
+
+Id=backup.service
+Description=Nightly backup
+LoadState=loaded
+ActiveState=failed
+SubState=failed
+ActiveEnterTimestampMonotonic=0
+NRestarts=0
+
+Id=missing.service
+Description=missing.service
+LoadState=not-found
+ActiveState=inactive
+SubState=dead
+ActiveEnterTimestampMonotonic=0
+NRestarts=0
+";
+
+    fn units(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_one_block_per_unit_in_order() {
+        let statuses = parse_show(SHOW, &units(&["nginx", "backup", "missing"]), Some(65_000_000));
+
+        let nginx = &statuses[0];
+        assert_eq!(nginx.name, "nginx");
+        assert_eq!(nginx.description, "A high performance web server");
+        assert_eq!((nginx.active_state.as_str(), nginx.sub_state.as_str()), ("active", "running"));
+        assert_eq!(nginx.active_secs, Some(60));
+        assert_eq!(nginx.restarts, Some(2));
+        assert_eq!(nginx.health(), HealthStatus::Healthy);
+
+        assert_eq!(statuses[1].health(), HealthStatus::Unhealthy);
+        assert_eq!(statuses[1].active_secs, None);
+        assert_eq!(statuses[2].load_state, "not-found");
+        assert_eq!(statuses[2].health(), HealthStatus::Degraded);
+    }
+
+    #[test]
+    fn units_missing_from_the_output_are_unknown() {
+        let statuses = parse_show("", &units(&["nginx"]), None);
+        assert_eq!(statuses[0].active_state, "unknown");
+        assert_eq!(statuses[0].description, "missing from systemctl output");
+    }
+
+    #[test]
+    fn active_time_needs_a_clock() {
+        let statuses = parse_show(SHOW, &units(&["nginx"]), None);
+        assert_eq!(statuses[0].active_secs, None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Fixtures shared by the unit tests
+
+use crate::cgroup::ContainerInfo;
+use crate::sampler::Snapshot;
+use crate::utils::{CpuInfo, DiskUsage, MemoryInfo, SystemInfo};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A host with one disk, idle apart from the given memory and disk usage
+pub fn test_snapshot(memory_percent: u64, disk_percent: u64) -> Snapshot {
+    Snapshot {
+        taken_at: chrono::Utc::now(),
+        system: SystemInfo {
+            cpu: CpuInfo::default(),
+            memory: MemoryInfo {
+                total: 100,
+                available: 100 - memory_percent,
+                used: memory_percent,
+            },
+            kernel_version: String::new(),
+            os_version: String::new(),
+            uptime: 0,
+        },
+        disks: vec![DiskUsage {
+            name: "sda1".to_string(),
+            mount_point: "/".to_string(),
+            file_system: "ext4".to_string(),
+            total: 100,
+            available: 100 - disk_percent,
+            inodes: None,
+        }],
+        networks: Vec::new(),
+        sensors: Vec::new(),
+        container: ContainerInfo::default(),
+    }
+}
+
+/// A plain HTTP server on a free local port. It answers each request with the next of `responses`
+/// and stops listening once they run out, so later requests are refused.
+pub struct Stub {
+    pub url: String,
+    hits: Arc<AtomicUsize>,
+}
+
+impl Stub {
+    pub fn serve(responses: &[(u16, &str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+
+        let responses: Vec<(u16, String)> = responses.iter().map(|(status, body)| (*status, body.to_string())).collect();
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                // Read the whole request head, closing with unread input would reset the connection
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        Self { url, hits }
+    }
+
+    /// Requests answered so far
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,155 @@
+use crate::utils::truncate;
+use crate::{Context, Data, Error};
+use chrono::{DateTime, Utc};
+use std::time::{Duration, Instant};
+
+/// How a command was invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvocationKind {
+    Slash,
+    Prefix,
+}
+
+impl InvocationKind {
+    fn of(ctx: Context<'_>) -> Self {
+        match ctx {
+            poise::Context::Application(_) => InvocationKind::Slash,
+            poise::Context::Prefix(_) => InvocationKind::Prefix,
+        }
+    }
+
+    /// How the kind is stored in the stats database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InvocationKind::Slash => "slash",
+            InvocationKind::Prefix => "prefix",
+        }
+    }
+}
+
+/// One command invocation, successful or not
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    /// Qualified name, so subcommands are told apart from their parent
+    pub command: String,
+    pub kind: InvocationKind,
+    pub success: bool,
+    /// What went wrong, for failed invocations
+    pub error: Option<String>,
+    /// From `pre_command` to the command returning, zero if it was stopped before it ran
+    /// (failed checks, cooldowns, missing permissions)
+    pub duration: Duration,
+    pub guild_id: Option<u64>,
+    /// `None` for invocations recorded before channels were stored
+    pub channel_id: Option<u64>,
+    pub user_id: u64,
+    pub at: DateTime<Utc>,
+}
+
+/// Totals for one command over everything in the stats database
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandTotals {
+    pub uses: u64,
+    pub failures: u64,
+    pub slash: u64,
+    pub prefix: u64,
+    pub total_duration: Duration,
+}
+
+/// How long one command takes and how often it fails
+#[derive(Debug, Clone)]
+pub struct CommandPerformance {
+    pub command: String,
+    pub uses: u64,
+    pub failures: u64,
+    /// Run time percentiles in milliseconds, `None` if it never got to run
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+impl CommandPerformance {
+    pub fn error_rate(&self) -> f64 {
+        if self.uses == 0 {
+            return 0.0;
+        }
+        self.failures as f64 / self.uses as f64 * 100.0
+    }
+}
+
+/// A failed invocation, for the list of recent errors
+#[derive(Debug, Clone)]
+pub struct RecordedError {
+    pub command: String,
+    pub error: String,
+    pub at: DateTime<Utc>,
+}
+
+impl CommandTotals {
+    pub fn average_duration(&self) -> Duration {
+        if self.uses == 0 {
+            return Duration::ZERO;
+        }
+        self.total_duration / self.uses as u32
+    }
+}
+
+/// Framework `pre_command` hook, remembers when the command started
+pub async fn pre_command(ctx: Context<'_>) {
+    ctx.set_invocation_data(Instant::now()).await;
+}
+
+/// Framework `post_command` hook, only reached when the command returned `Ok`
+pub async fn post_command(ctx: Context<'_>) {
+    record(ctx, None).await;
+}
+
+/// Count a command that errored or never got to run, called from `on_error`
+pub async fn record_failure(error: &poise::FrameworkError<'_, Data, Error>) {
+    if let Some(ctx) = error.ctx() {
+        let description = match error {
+            poise::FrameworkError::Command { error, .. } => error.to_string(),
+            error => error.to_string(),
+        };
+        record(ctx, Some(truncate(&description, 200))).await;
+    }
+}
+
+async fn record(ctx: Context<'_>, error: Option<String>) {
+    let duration = ctx
+        .invocation_data::<Instant>()
+        .await
+        .map(|start| start.elapsed())
+        .unwrap_or_default();
+
+    let invocation = Invocation {
+        command: ctx.command().qualified_name.clone(),
+        kind: InvocationKind::of(ctx),
+        success: error.is_none(),
+        error,
+        duration,
+        guild_id: ctx.guild_id().map(|guild| guild.get()),
+        channel_id: Some(ctx.channel_id().get()),
+        user_id: ctx.author().id.get(),
+        at: Utc::now(),
+    };
+    tracing::debug!(
+        "Command `{}` ({}) by {} in {:?}: {} after {:?}",
+        invocation.command,
+        invocation.kind.as_str(),
+        invocation.user_id,
+        invocation.guild_id,
+        if invocation.success { "ok" } else { "failed" },
+        invocation.duration
+    );
+    let slow = std::time::Duration::from_millis(ctx.data().config.slow_command_ms);
+    if invocation.duration >= slow {
+        tracing::warn!("Command `{}` took {:?}", invocation.command, invocation.duration);
+    }
+
+    // SQLite writes block, keep them off the async workers
+    let storage = ctx.data().storage.clone();
+    if let Err(e) = tokio::task::spawn_blocking(move || storage.record(&invocation)).await {
+        tracing::error!("Recording a command invocation panicked: {}", e);
+    }
+}
@@ -1,14 +1,35 @@
-use sysinfo::{System, SystemExt, CpuExt, NetworkExt};
+use sysinfo::{System, SystemExt, ComponentExt, CpuExt, DiskExt, NetworkExt, PidExt, ProcessExt, UserExt};
 use std::process::Command;
+use serde::Serialize;
 
-pub fn get_system_info() -> SystemInfo {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    
+pub mod network;
+pub mod public_ip;
+
+/// Version of the serialized `SystemInfo` schema exposed by the HTTP API.
+/// Bump this whenever a field is renamed, removed or changes meaning.
+///
+/// - 2: `cpu.usage` covers all cores instead of the first one; adds core counts, `per_core_usage`
+///   and `load_average`
+/// - 3: `memory_usage_percent` in `/v1/health` is relative to the cgroup limit inside containers
+/// - 4: `status` in `/v1/health` comes from the weighted health score instead of the fixed ladder
+/// - 5: `/v1/system` follows the visibility policy, IPs and versions may be redacted, empty or missing;
+///   `services` and `checks` in `/v1/health` come from the last background probe, see `probed_at`
+pub const SCHEMA_VERSION: u32 = 5;
+
+pub fn get_system_info(sys: &System) -> SystemInfo {
+    let load = sys.load_average();
     let cpu_info = sys.cpus().first().map(|cpu| CpuInfo {
         brand: cpu.brand().to_string(),
         frequency: cpu.frequency(),
-        usage: cpu.cpu_usage(),
+        usage: sys.global_cpu_info().cpu_usage(),
+        physical_cores: sys.physical_core_count(),
+        logical_cores: sys.cpus().len(),
+        per_core_usage: sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect(),
+        load_average: LoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        },
     }).unwrap_or_default();
     
     let memory = MemoryInfo {
@@ -29,39 +50,301 @@ pub fn get_system_info() -> SystemInfo {
     }
 }
 
-pub async fn get_public_ip() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let response = reqwest::get("https://api.ipify.org").await?;
-    let ip = response.text().await?;
-    Ok(ip)
+/// Filesystems that don't hold user data and only clutter the disk list
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs", "binfmt_misc", "bpf", "cgroup", "cgroup2", "configfs", "debugfs", "devpts",
+    "devtmpfs", "efivarfs", "fusectl", "hugetlbfs", "mqueue", "nsfs", "proc", "pstore",
+    "ramfs", "securityfs", "squashfs", "sysfs", "tmpfs", "tracefs",
+];
+
+pub fn get_disk_usage(sys: &System) -> Vec<DiskUsage> {
+    let mut disks: Vec<DiskUsage> = Vec::new();
+    
+    for disk in sys.disks() {
+        let file_system = String::from_utf8_lossy(disk.file_system()).to_string();
+        if PSEUDO_FILESYSTEMS.contains(&file_system.as_str()) || disk.total_space() == 0 {
+            continue;
+        }
+        
+        let usage = DiskUsage {
+            name: disk.name().to_string_lossy().to_string(),
+            mount_point: disk.mount_point().to_string_lossy().to_string(),
+            file_system,
+            total: disk.total_space(),
+            available: disk.available_space(),
+            inodes: inode_counts(disk.mount_point()),
+        };
+        
+        // Bind mounts show the same device several times, keep its shortest mount point
+        match disks.iter_mut().find(|seen| seen.name == usage.name) {
+            Some(seen) if usage.mount_point.len() < seen.mount_point.len() => *seen = usage,
+            Some(_) => {}
+            None => disks.push(usage),
+        }
+    }
+    
+    disks
+}
+
+/// Total and free inodes of the filesystem at `mount_point`, `None` where it has no fixed inode table
+fn inode_counts(mount_point: &std::path::Path) -> Option<InodeUsage> {
+    use std::os::unix::ffi::OsStrExt;
+    
+    let path = std::ffi::CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `path` is a valid NUL-terminated string and `stat` is a plain C struct
+    // that statvfs fully initializes on success.
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        stat
+    };
+    
+    // btrfs and friends allocate inodes dynamically and report zero
+    if stat.f_files == 0 {
+        return None;
+    }
+    Some(InodeUsage {
+        total: stat.f_files as u64,
+        free: stat.f_ffree as u64,
+    })
+}
+
+pub fn get_processes(sys: &System) -> Vec<ProcessInfo> {
+    sys.processes().values().map(|process| ProcessInfo {
+        pid: process.pid().as_u32(),
+        name: process.name().to_string(),
+        user: process
+            .user_id()
+            .and_then(|uid| sys.get_user_by_id(uid))
+            .map(|user| user.name().to_string())
+            .unwrap_or_else(|| "?".to_string()),
+        cpu_usage: process.cpu_usage(),
+        memory: process.memory(),
+        run_time: process.run_time(),
+    }).collect()
+}
+
+pub fn get_sensors(sys: &System) -> Vec<SensorReading> {
+    sys.components().iter()
+        // Some drivers expose sensors that never report a reading
+        .filter(|component| component.temperature().is_finite())
+        .map(|component| SensorReading {
+            label: component.label().to_string(),
+            temperature: component.temperature(),
+            max: component.max(),
+            critical: component.critical().filter(|critical| critical.is_finite() && *critical > 0.0),
+        })
+        .collect()
 }
 
 pub fn get_local_ip() -> Option<String> {
     local_ip_address::local_ip().ok().map(|ip| ip.to_string())
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SystemInfo {
     pub cpu: CpuInfo,
     pub memory: MemoryInfo,
     pub kernel_version: String,
     pub os_version: String,
+    #[serde(rename = "uptime_secs")]
     pub uptime: u64,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct CpuInfo {
     pub brand: String,
     pub frequency: u64,
+    /// Usage across all cores
     pub usage: f32,
+    pub physical_cores: Option<usize>,
+    pub logical_cores: usize,
+    pub per_core_usage: Vec<f32>,
+    pub load_average: LoadAverage,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MemoryInfo {
     pub total: u64,
     pub available: u64,
     pub used: u64,
 }
 
+impl MemoryInfo {
+    pub fn usage_percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.used as f64 / self.total as f64) * 100.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+    /// Percent of one core, so busy multi-threaded processes can exceed 100
+    pub cpu_usage: f32,
+    /// Resident memory in bytes
+    pub memory: u64,
+    pub run_time: u64,
+}
+
+/// Temperatures in °C
+#[derive(Debug, Clone, Serialize)]
+pub struct SensorReading {
+    pub label: String,
+    pub temperature: f32,
+    /// Highest temperature seen since the bot started
+    pub max: f32,
+    pub critical: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub name: String,
+    pub mount_point: String,
+    pub file_system: String,
+    pub total: u64,
+    pub available: u64,
+    pub inodes: Option<InodeUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct InodeUsage {
+    pub total: u64,
+    pub free: u64,
+}
+
+impl DiskUsage {
+    pub fn usage_percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        ((self.total - self.available) as f64 / self.total as f64) * 100.0
+    }
+    
+    pub fn inode_usage_percent(&self) -> Option<f64> {
+        self.inodes.map(|inodes| ((inodes.total - inodes.free) as f64 / inodes.total as f64) * 100.0)
+    }
+}
+
+/// Replace the file at `path` with `contents` by writing next to it and renaming,
+/// so a crash never leaves half a file behind
+pub fn write_atomic(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Discord rejects embeds with more fields than this
+pub const MAX_EMBED_FIELDS: usize = 25;
+/// ... or with more characters than this across title, description, fields and footer
+pub const MAX_EMBED_CHARS: usize = 6000;
+/// ... or a description longer than this
+pub const MAX_EMBED_DESCRIPTION: usize = 4096;
+
+/// A code block of `header` and as many of `rows` (each ending in a newline) as fit in `max`
+/// characters, itself capped at the description limit
+pub fn code_block_description(header: &str, rows: &[String], max: usize) -> String {
+    const FENCES: usize = "```\n```".len();
+    let max = max.min(MAX_EMBED_DESCRIPTION);
+    let mut table = header.to_string();
+    for row in rows {
+        // Bytes, which are never fewer than characters
+        if FENCES + table.len() + row.len() > max {
+            break;
+        }
+        table.push_str(row);
+    }
+    format!("```\n{}```", table)
+}
+
+/// Counts fields going into an embed against Discord's field count and total length limits
+pub struct FieldBudget {
+    chars: usize,
+    fields: usize,
+    skipped: usize,
+}
+
+impl FieldBudget {
+    /// A budget with `chars` and `fields` held back for the title, footer and anything added later
+    pub fn new(reserved_chars: usize, reserved_fields: usize) -> Self {
+        Self {
+            chars: MAX_EMBED_CHARS.saturating_sub(reserved_chars),
+            fields: MAX_EMBED_FIELDS.saturating_sub(reserved_fields),
+            skipped: 0,
+        }
+    }
+
+    /// Whether a field with this name and value still fits, reserving room for it if so.
+    /// Once one field doesn't fit, every later one is skipped too, so lists stay in order.
+    pub fn fits(&mut self, name: &str, value: &str) -> bool {
+        let len = name.chars().count() + value.chars().count();
+        if self.skipped > 0 || self.fields == 0 || len > self.chars {
+            self.skipped += 1;
+            return false;
+        }
+        self.chars -= len;
+        self.fields -= 1;
+        true
+    }
+
+    /// How many fields were left out
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+/// Shorten `text` to `max` characters, marking the cut with an ellipsis
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Join `lines` with newlines in at most `max` characters, replacing the lines
+/// that don't fit with a "+N more" line
+pub fn join_lines(lines: &[String], max: usize) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let separator = usize::from(!out.is_empty());
+        let line_len = line.chars().count();
+        let rest = lines.len() - index - 1;
+        // Whatever follows still needs room for its own "+N more" line
+        let reserve = if rest > 0 { format!("\n+{} more", rest).chars().count() } else { 0 };
+        if len + separator + line_len + reserve > max {
+            let more = format!("+{} more", lines.len() - index);
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&more);
+            return truncate(&out, max);
+        }
+        if separator == 1 {
+            out.push('\n');
+        }
+        out.push_str(line);
+        len += separator + line_len;
+    }
+    out
+}
+
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
@@ -91,3 +374,85 @@ pub fn format_uptime(seconds: u64) -> String {
         format!("{}s", secs)
     }
 }
+
+/// Min/avg/max/p95 of a series of samples
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    pub p95: f64,
+}
+
+pub fn summarize(values: &[f64]) -> Option<Summary> {
+    if values.is_empty() {
+        return None;
+    }
+    
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    
+    Some(Summary {
+        min: sorted[0],
+        avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        max: sorted[sorted.len() - 1],
+        p95: percentile(&sorted, 95.0),
+    })
+}
+
+/// Nearest-rank percentile of an already sorted, non-empty slice
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Render values as a Unicode sparkline at most `width` characters wide
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    if values.is_empty() || width == 0 {
+        return String::new();
+    }
+    
+    // Average neighbouring samples together when there are more than fit
+    let bucket_size = values.len().div_ceil(width);
+    let buckets: Vec<f64> = values
+        .chunks(bucket_size)
+        .map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64)
+        .collect();
+    
+    let min = buckets.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = buckets.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    
+    buckets.iter().map(|value| {
+        if range <= f64::EPSILON {
+            BARS[BARS.len() / 2]
+        } else {
+            let index = ((value - min) / range * (BARS.len() - 1) as f64).round() as usize;
+            BARS[index.min(BARS.len() - 1)]
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let sorted: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 50.0), 5.0);
+        assert_eq!(percentile(&sorted, 95.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 100.0), 10.0);
+        assert_eq!(percentile(&[42.0], 99.0), 42.0);
+    }
+
+    #[test]
+    fn code_block_description_keeps_whole_rows_within_the_limit() {
+        let rows = vec!["a\n".to_string(); 10];
+        assert_eq!(code_block_description("h\n", &rows, 15), "```\nh\na\na\na\n```");
+        let rows = vec![format!("{}\n", "x".repeat(99)); 100];
+        assert!(code_block_description("", &rows, usize::MAX).len() <= MAX_EMBED_DESCRIPTION);
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use sysinfo::{NetworkExt, NetworksExt, System, SystemExt};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: Option<String>,
+    pub addresses: Vec<IpAddr>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    /// Bytes per second received since the previous sample
+    pub rx_rate: f64,
+    /// Bytes per second sent since the previous sample
+    pub tx_rate: f64,
+}
+
+/// Every interface with its counters, `elapsed` being the time since the previous refresh
+pub fn get_network_interfaces(sys: &System, elapsed: Duration) -> Vec<NetworkInterface> {
+    let addresses = local_ip_address::list_afinet_netifas().unwrap_or_default();
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    
+    let mut interfaces: Vec<NetworkInterface> = sys.networks().iter().map(|(name, data)| {
+        let mac = data.mac_address();
+        NetworkInterface {
+            name: name.clone(),
+            mac: (!mac.is_unspecified()).then(|| mac.to_string()),
+            addresses: addresses
+                .iter()
+                .filter(|(interface, _)| interface == name)
+                .map(|(_, ip)| *ip)
+                .collect(),
+            rx_bytes: data.total_received(),
+            tx_bytes: data.total_transmitted(),
+            rx_packets: data.total_packets_received(),
+            tx_packets: data.total_packets_transmitted(),
+            rx_errors: data.total_errors_on_received(),
+            tx_errors: data.total_errors_on_transmitted(),
+            rx_rate: data.received() as f64 / seconds,
+            tx_rate: data.transmitted() as f64 / seconds,
+        }
+    }).collect();
+    
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Count IPv4 and IPv6 TCP sockets by state, from `/proc/net/tcp*`
+pub fn get_tcp_states() -> BTreeMap<&'static str, u64> {
+    let mut states = BTreeMap::new();
+    
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(table) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        
+        // Skip the header; the fourth column is the state as a hex code
+        for line in table.lines().skip(1) {
+            if let Some(state) = line.split_whitespace().nth(3) {
+                *states.entry(tcp_state_name(state)).or_insert(0) += 1;
+            }
+        }
+    }
+    
+    states
+}
+
+fn tcp_state_name(code: &str) -> &'static str {
+    match code {
+        "01" => "ESTABLISHED",
+        "02" => "SYN_SENT",
+        "03" => "SYN_RECV",
+        "04" => "FIN_WAIT1",
+        "05" => "FIN_WAIT2",
+        "06" => "TIME_WAIT",
+        "07" => "CLOSE",
+        "08" => "CLOSE_WAIT",
+        "09" => "LAST_ACK",
+        "0A" => "LISTEN",
+        "0B" => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+
+/// A failed lookup is retried after this long instead of the full TTL
+const FAILURE_RETRY: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_IPV4_PROVIDERS: &[&str] = &[
+    "https://api.ipify.org",
+    "https://ipv4.icanhazip.com",
+    "https://v4.ident.me",
+];
+
+pub const DEFAULT_IPV6_PROVIDERS: &[&str] = &[
+    "https://api6.ipify.org",
+    "https://ipv6.icanhazip.com",
+    "https://v6.ident.me",
+];
+
+/// The host's public addresses, either of which may be missing
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PublicIps {
+    pub v4: Option<Ipv4Addr>,
+    pub v6: Option<Ipv6Addr>,
+}
+
+/// Looks the public IPv4 and IPv6 address up from a list of plain-text providers.
+///
+/// Providers are tried in order, each bounded by `timeout`, until one answers
+/// with an address of the right family. Answers are reused for `ttl`; when every
+/// provider fails the last known address is returned instead.
+pub struct PublicIpCache {
+    ttl: Duration,
+    v4: Lookup,
+    v6: Lookup,
+}
+
+impl PublicIpCache {
+    pub fn new(ttl: Duration, timeout: Duration, v4_providers: Vec<String>, v6_providers: Vec<String>) -> Self {
+        Self {
+            ttl,
+            v4: Lookup::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), timeout, v4_providers),
+            v6: Lookup::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), timeout, v6_providers),
+        }
+    }
+
+    pub async fn get(&self) -> PublicIps {
+        let (v4, v6) = tokio::join!(self.v4.get(self.ttl), self.v6.get(self.ttl));
+        PublicIps {
+            v4: v4.and_then(|ip| match ip {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            }),
+            v6: v6.and_then(|ip| match ip {
+                IpAddr::V6(ip) => Some(ip),
+                IpAddr::V4(_) => None,
+            }),
+        }
+    }
+}
+
+struct Cached {
+    checked_at: Instant,
+    succeeded: bool,
+    ip: Option<IpAddr>,
+}
+
+/// One address family's providers and cached answer
+struct Lookup {
+    family: IpAddr,
+    /// Bound to the unspecified address of the family, so the request can only go out over it
+    client: Option<reqwest::Client>,
+    providers: Vec<String>,
+    cached: tokio::sync::Mutex<Option<Cached>>,
+}
+
+impl Lookup {
+    fn new(bind: IpAddr, timeout: Duration, providers: Vec<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .local_address(bind)
+            .timeout(timeout)
+            .build()
+            .map_err(|e| tracing::warn!("Could not build public IP client: {}", e))
+            .ok();
+
+        Self {
+            family: bind,
+            client,
+            providers,
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    async fn get(&self, ttl: Duration) -> Option<IpAddr> {
+        let mut cached = self.cached.lock().await;
+        if let Some(entry) = cached.as_ref() {
+            let max_age = if entry.succeeded { ttl } else { FAILURE_RETRY.min(ttl) };
+            if entry.checked_at.elapsed() < max_age {
+                return entry.ip;
+            }
+        }
+
+        let fresh = self.lookup().await;
+        // A stale answer is still better than none
+        let ip = fresh.or_else(|| cached.as_ref().and_then(|entry| entry.ip));
+        *cached = Some(Cached {
+            checked_at: Instant::now(),
+            succeeded: fresh.is_some(),
+            ip,
+        });
+        ip
+    }
+
+    async fn lookup(&self) -> Option<IpAddr> {
+        let client = self.client.as_ref()?;
+        for provider in &self.providers {
+            match query(client, provider).await {
+                Ok(ip) if ip.is_ipv4() == self.family.is_ipv4() => return Some(ip),
+                Ok(ip) => tracing::debug!("Public IP provider {} answered with {}, wrong family", provider, ip),
+                Err(e) => tracing::debug!("Public IP provider {} failed: {}", provider, e),
+            }
+        }
+        // Plenty of hosts have no IPv6 at all, that isn't worth a warning
+        if self.family.is_ipv4() && !self.providers.is_empty() {
+            tracing::warn!("Public IPv4 lookup failed with all {} providers", self.providers.len());
+        }
+        None
+    }
+}
+
+async fn query(client: &reqwest::Client, provider: &str) -> Result<IpAddr, crate::Error> {
+    let body = client.get(provider).send().await?.error_for_status()?.text().await?;
+    Ok(body.trim().parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Stub;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    fn lookup(providers: &[&Stub]) -> Lookup {
+        Lookup::new(
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Duration::from_secs(2),
+            providers.iter().map(|stub| stub.url.clone()).collect(),
+        )
+    }
+
+    /// Pretend the cached answer was looked up `by` earlier
+    async fn age(lookup: &Lookup, by: Duration) {
+        lookup.cached.lock().await.as_mut().unwrap().checked_at -= by;
+    }
+
+    fn ip(raw: &str) -> Option<IpAddr> {
+        Some(raw.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_next_provider() {
+        let broken = Stub::serve(&[(500, "oops")]);
+        let wrong_family = Stub::serve(&[(200, "2001:db8::7")]);
+        let working = Stub::serve(&[(200, "203.0.113.7\n")]);
+        let lookup = lookup(&[&broken, &wrong_family, &working]);
+
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        assert_eq!((broken.hits(), wrong_family.hits(), working.hits()), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn reuses_the_answer_within_the_ttl() {
+        let stub = Stub::serve(&[(200, "203.0.113.7"), (200, "203.0.113.8")]);
+        let lookup = lookup(&[&stub]);
+
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        assert_eq!(stub.hits(), 1);
+
+        age(&lookup, TTL).await;
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.8"));
+    }
+
+    #[tokio::test]
+    async fn retries_a_failure_sooner_than_the_ttl() {
+        let stub = Stub::serve(&[(503, ""), (200, "203.0.113.7")]);
+        let lookup = lookup(&[&stub]);
+
+        assert_eq!(lookup.get(TTL).await, None);
+        assert_eq!(lookup.get(TTL).await, None);
+        assert_eq!(stub.hits(), 1);
+
+        age(&lookup, FAILURE_RETRY).await;
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        assert_eq!(stub.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_stale_answer_when_every_provider_fails() {
+        // Refuses connections after its one answer
+        let stub = Stub::serve(&[(200, "203.0.113.7")]);
+        let broken = Stub::serve(&[(500, ""), (500, "")]);
+        let lookup = lookup(&[&stub, &broken]);
+
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        age(&lookup, TTL).await;
+        assert_eq!(lookup.get(TTL).await, ip("203.0.113.7"));
+        assert_eq!(broken.hits(), 1);
+        assert!(!lookup.cached.lock().await.as_ref().unwrap().succeeded);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::Context;
+use poise::serenity_prelude as serenity;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Host details that shouldn't necessarily be posted to everyone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveField {
+    /// Public IPv4 and IPv6 address
+    PublicIp,
+    LocalIp,
+    KernelVersion,
+    OsVersion,
+    /// Addresses and MACs in `/network`
+    InterfaceAddresses,
+}
+
+impl SensitiveField {
+    fn default_visibility(self) -> Visibility {
+        match self {
+            SensitiveField::OsVersion => Visibility::Public,
+            _ => Visibility::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    /// Shown in full to admins, redacted for everyone else
+    Admin,
+    /// Never shown
+    Hidden,
+}
+
+/// Who sees which host details, from the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VisibilityConfig {
+    /// Overrides of the built-in defaults, for every guild
+    pub fields: HashMap<SensitiveField, Visibility>,
+    /// Overrides for single guilds, keyed by guild ID
+    pub guilds: HashMap<u64, HashMap<SensitiveField, Visibility>>,
+    /// Users who get the full view besides bot owners
+    pub admin_user_ids: Vec<u64>,
+    /// Guilds whose members with Administrator or Manage Server also get the full view there.
+    /// Anyone can make a guild and invite the bot, so this is opt-in.
+    pub trusted_guild_ids: Vec<u64>,
+}
+
+impl VisibilityConfig {
+    pub fn visibility(&self, field: SensitiveField, guild: Option<serenity::GuildId>) -> Visibility {
+        guild
+            .and_then(|guild| self.guilds.get(&guild.get()))
+            .and_then(|fields| fields.get(&field))
+            .or_else(|| self.fields.get(&field))
+            .copied()
+            .unwrap_or_else(|| field.default_visibility())
+    }
+
+    /// Whether the admins of `guild` count as admins of the bot
+    pub fn trusts_guild_admins(&self, guild: Option<serenity::GuildId>) -> bool {
+        guild.is_some_and(|guild| self.trusted_guild_ids.contains(&guild.get()))
+    }
+}
+
+/// What the user running a command may see
+pub struct View<'a> {
+    config: &'a VisibilityConfig,
+    guild: Option<serenity::GuildId>,
+    /// Admin-only details are shown unredacted
+    pub full: bool,
+}
+
+impl<'a> View<'a> {
+    pub fn for_context(ctx: Context<'a>) -> Self {
+        let config = &ctx.data().config.visibility;
+        let author = ctx.author().id;
+        let listed = ctx.framework().options().owners.contains(&author) || config.admin_user_ids.contains(&author.get());
+
+        let full = match ctx {
+            poise::Context::Application(app) => {
+                listed
+                    || config.trusts_guild_admins(ctx.guild_id())
+                        && app
+                        .interaction
+                        .member
+                        .as_ref()
+                        .and_then(|member| member.permissions)
+                        .is_some_and(|permissions| permissions.administrator() || permissions.manage_guild())
+            }
+            // Prefix replies can't be ephemeral, so the full view is only safe in DMs
+            poise::Context::Prefix(_) => listed && ctx.guild_id().is_none(),
+        };
+
+        Self {
+            config,
+            guild: ctx.guild_id(),
+            full,
+        }
+    }
+
+    /// What anyone may see, for the unauthenticated HTTP API
+    pub fn public(config: &'a VisibilityConfig) -> Self {
+        Self {
+            config,
+            guild: None,
+            full: false,
+        }
+    }
+
+    /// Whether the reply carries admin-only details and must only be seen by the invoker
+    pub fn ephemeral(&self) -> bool {
+        self.full && self.guild.is_some()
+    }
+
+    /// Whether any of `fields` is shown redacted to this viewer
+    pub fn redacts_any(&self, fields: &[SensitiveField]) -> bool {
+        !self.full
+            && fields
+                .iter()
+                .any(|field| self.config.visibility(*field, self.guild) == Visibility::Admin)
+    }
+
+    pub fn hides(&self, field: SensitiveField) -> bool {
+        self.config.visibility(field, self.guild) == Visibility::Hidden
+    }
+
+    /// `value` as this viewer may see it, `None` when it isn't shown at all
+    pub fn show(&self, field: SensitiveField, value: &str) -> Option<String> {
+        match self.config.visibility(field, self.guild) {
+            Visibility::Hidden => None,
+            Visibility::Public => Some(value.to_string()),
+            Visibility::Admin if self.full => Some(value.to_string()),
+            Visibility::Admin => Some(redact(field, value)),
+        }
+    }
+}
+
+fn redact(field: SensitiveField, value: &str) -> String {
+    match field {
+        SensitiveField::PublicIp | SensitiveField::LocalIp | SensitiveField::InterfaceAddresses => redact_address(value),
+        // `6.8.0-45-generic` becomes `6.8.x`
+        SensitiveField::KernelVersion => {
+            let parts: Vec<&str> = value.split(['.', '-']).take(2).collect();
+            format!("{}.x", parts.join("."))
+        }
+        // Only the first word, `Linux 22.04 Ubuntu` becomes `Linux`
+        SensitiveField::OsVersion => value.split_whitespace().next().unwrap_or("redacted").to_string(),
+    }
+}
+
+/// `203.0.113.7` → `203.0.113.x`, `2001:db8::7` → `2001:db8:…`, MACs keep their vendor prefix
+fn redact_address(value: &str) -> String {
+    match value.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.x", a, b, c)
+        }
+        Ok(std::net::IpAddr::V6(ip)) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}:…", segments[0], segments[1])
+        }
+        Err(_) if value.split(':').count() == 6 => {
+            let vendor: Vec<&str> = value.split(':').take(3).collect();
+            format!("{}:xx:xx:xx", vendor.join(":"))
+        }
+        Err(_) => "redacted".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_keep_their_network_part() {
+        assert_eq!(redact_address("203.0.113.7"), "203.0.113.x");
+        assert_eq!(redact_address("2001:db8::7"), "2001:db8:…");
+        assert_eq!(redact_address("52:54:00:12:34:56"), "52:54:00:xx:xx:xx");
+        assert_eq!(redact_address("not an address"), "redacted");
+    }
+
+    #[test]
+    fn versions_keep_only_their_start() {
+        assert_eq!(redact(SensitiveField::KernelVersion, "6.8.0-45-generic"), "6.8.x");
+        assert_eq!(redact(SensitiveField::OsVersion, "Linux 22.04 Ubuntu"), "Linux");
+        assert_eq!(redact(SensitiveField::OsVersion, ""), "redacted");
+        assert_eq!(redact(SensitiveField::LocalIp, "192.168.1.20"), "192.168.1.x");
+    }
+
+    #[test]
+    fn public_view_redacts_admin_fields_and_drops_hidden_ones() {
+        let config = VisibilityConfig {
+            fields: HashMap::from([(SensitiveField::LocalIp, Visibility::Hidden)]),
+            ..Default::default()
+        };
+        let view = View::public(&config);
+        assert_eq!(view.show(SensitiveField::PublicIp, "203.0.113.7").as_deref(), Some("203.0.113.x"));
+        assert_eq!(view.show(SensitiveField::LocalIp, "192.168.1.20"), None);
+        assert_eq!(view.show(SensitiveField::OsVersion, "Linux 22.04 Ubuntu").as_deref(), Some("Linux 22.04 Ubuntu"));
+    }
+
+    #[test]
+    fn guild_overrides_win_over_global_fields() {
+        let config = VisibilityConfig {
+            fields: HashMap::from([(SensitiveField::KernelVersion, Visibility::Public)]),
+            guilds: HashMap::from([(7, HashMap::from([(SensitiveField::KernelVersion, Visibility::Hidden)]))]),
+            ..Default::default()
+        };
+        let guild = Some(serenity::GuildId::new(7));
+        assert_eq!(config.visibility(SensitiveField::KernelVersion, guild), Visibility::Hidden);
+        assert_eq!(config.visibility(SensitiveField::KernelVersion, None), Visibility::Public);
+        assert_eq!(config.visibility(SensitiveField::PublicIp, guild), Visibility::Admin);
+    }
+
+    #[test]
+    fn guild_admins_are_only_trusted_in_listed_guilds() {
+        assert!(!VisibilityConfig::default().trusts_guild_admins(Some(serenity::GuildId::new(7))));
+
+        let config = VisibilityConfig {
+            trusted_guild_ids: vec![7],
+            ..Default::default()
+        };
+        assert!(config.trusts_guild_admins(Some(serenity::GuildId::new(7))));
+        assert!(!config.trusts_guild_admins(Some(serenity::GuildId::new(8))));
+        assert!(!config.trusts_guild_admins(None));
+    }
+}